@@ -0,0 +1,167 @@
+use crate::parser::GenericCommand;
+use core::fmt::Write;
+
+/// A `core::fmt::Write` implementation which fills a caller-provided buffer.
+pub(crate) struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub(crate) fn new(buffer: &'a mut [u8]) -> SliceWriter<'a> {
+        SliceWriter { buffer, len: 0 }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a> Write for SliceWriter<'a> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let bytes = s.as_bytes();
+        let end = self.len + bytes.len();
+        if end > self.buffer.len() {
+            return Err(core::fmt::Error);
+        }
+
+        self.buffer[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Writes the command in the text format accepted by `parser::Parser`, including the line
+/// terminator.
+///
+/// Fails without writing anything for a `WriteData` with an empty payload, which the text format
+/// cannot express.
+pub fn encode<W, const N: usize>(command: &GenericCommand<N>, w: &mut W) -> core::fmt::Result
+where
+    W: Write,
+{
+    match command {
+        GenericCommand::ReadByte(addr) => write!(w, "rb 0x{:08X}\r\n", addr),
+        GenericCommand::WriteByte(addr, data) => write!(w, "wb 0x{:08X} 0x{:02X}\r\n", addr, data),
+        GenericCommand::ReadData(addr, len) => write!(w, "rd 0x{:08X} {}\r\n", addr, len),
+        GenericCommand::WritePage(page) => write!(w, "wp 0x{:02X}\r\n", page),
+        GenericCommand::SetDevice(device_name) => write!(w, "sd {}\r\n", device_name.mnemonic()),
+        GenericCommand::WriteData(_, data) if data.is_empty() => Err(core::fmt::Error),
+        GenericCommand::WriteData(addr, data) => {
+            write!(w, "wd 0x{:08X}", addr)?;
+            for b in data.as_slice() {
+                write!(w, " 0x{:02X}", b)?;
+            }
+            write!(w, "\r\n")
+        }
+        GenericCommand::Fill(addr, len, data) => {
+            write!(w, "fill 0x{:08X} {} 0x{:02X}\r\n", addr, len, data)
+        }
+        GenericCommand::Erase => write!(w, "erase\r\n"),
+        GenericCommand::Verify(addr, len, crc) => {
+            write!(w, "verify 0x{:08X} {} 0x{:08X}\r\n", addr, len, crc)
+        }
+        GenericCommand::Crc(addr, len) => write!(w, "crc 0x{:08X} {}\r\n", addr, len),
+        GenericCommand::Identify => write!(w, "id\r\n"),
+        GenericCommand::Version => write!(w, "ver\r\n"),
+        GenericCommand::Status => write!(w, "st\r\n"),
+        GenericCommand::Probe => write!(w, "probe\r\n"),
+    }
+}
+
+/// Writes the command into the buffer and returns the number of bytes written.
+///
+/// Fails if the buffer is too small to hold the whole command or if `encode` fails.
+pub fn encode_to_slice<const N: usize>(
+    command: &GenericCommand<N>,
+    buffer: &mut [u8],
+) -> Result<usize, core::fmt::Error> {
+    let mut w = SliceWriter::new(buffer);
+    encode(command, &mut w)?;
+    Ok(w.len())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoder::{encode, encode_to_slice};
+    use crate::parser::{Command, DeviceName, Parser};
+    use crate::reader::StandardReader;
+    use crate::util::ByteBuffer;
+
+    fn expect_round_trip(command: Command) {
+        let mut buffer = [0; 128];
+        let len = encode_to_slice(&command, &mut buffer).unwrap();
+        let reader = StandardReader::new(&buffer[..len]);
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(command));
+    }
+
+    #[test]
+    fn encode_read_byte() {
+        let mut s = String::new();
+        encode(&Command::ReadByte(0x000E3B41), &mut s).unwrap();
+        assert_eq!(s, "rb 0x000E3B41\r\n");
+    }
+
+    #[test]
+    fn encode_write_byte() {
+        let mut s = String::new();
+        encode(&Command::WriteByte(0x00012000, 0x42), &mut s).unwrap();
+        assert_eq!(s, "wb 0x00012000 0x42\r\n");
+    }
+
+    #[test]
+    fn encode_to_small_slice() {
+        let mut buffer = [0; 8];
+        let command = Command::ReadByte(0x10);
+        assert!(encode_to_slice(&command, &mut buffer).is_err());
+    }
+
+    #[test]
+    fn round_trip_commands() {
+        expect_round_trip(Command::ReadByte(0x000E3B41));
+        expect_round_trip(Command::WriteByte(0x00012000, 0x42));
+        expect_round_trip(Command::WriteByte(0, 0xFF));
+        expect_round_trip(Command::ReadData(0x00000010, 32));
+        expect_round_trip(Command::WritePage(0x0F));
+        expect_round_trip(Command::WritePage(1023));
+        expect_round_trip(Command::WriteData(
+            0x00000010,
+            ByteBuffer::try_from_slice(&[0x00, 0x7F, 0x80, 0xFF]).unwrap(),
+        ));
+        expect_round_trip(Command::WriteData(
+            0x00012000,
+            ByteBuffer::try_from_slice(&[0x42; 16]).unwrap(),
+        ));
+        let empty = Command::WriteData(0x00000010, ByteBuffer::default());
+        assert!(encode_to_slice(&empty, &mut [0; 128]).is_err());
+        expect_round_trip(Command::Fill(0x00000100, 256, 0xFF));
+        expect_round_trip(Command::Erase);
+        expect_round_trip(Command::Verify(0x00000000, 9, 0x1234ABCD));
+        expect_round_trip(Command::Verify(0x00000000, 9, 0xCBF43926));
+        expect_round_trip(Command::Verify(0x00000000, 9, 0x80000000));
+        expect_round_trip(Command::Crc(0x00000010, 32));
+        expect_round_trip(Command::Identify);
+        expect_round_trip(Command::Version);
+        expect_round_trip(Command::Status);
+        expect_round_trip(Command::Probe);
+    }
+
+    #[test]
+    fn round_trip_set_device() {
+        expect_round_trip(Command::SetDevice(DeviceName::X00));
+        expect_round_trip(Command::SetDevice(DeviceName::X01));
+        expect_round_trip(Command::SetDevice(DeviceName::X02));
+        expect_round_trip(Command::SetDevice(DeviceName::X04));
+        expect_round_trip(Command::SetDevice(DeviceName::X08));
+        expect_round_trip(Command::SetDevice(DeviceName::X16));
+        expect_round_trip(Command::SetDevice(DeviceName::X32));
+        expect_round_trip(Command::SetDevice(DeviceName::X64));
+        expect_round_trip(Command::SetDevice(DeviceName::X128));
+        expect_round_trip(Command::SetDevice(DeviceName::X256));
+        expect_round_trip(Command::SetDevice(DeviceName::X512));
+        expect_round_trip(Command::SetDevice(DeviceName::XM01));
+        expect_round_trip(Command::SetDevice(DeviceName::XM02));
+    }
+}
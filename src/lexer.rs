@@ -0,0 +1,453 @@
+//! The lexer between a reader and a parser, for building command dialects on top of the scanner.
+
+use crate::parser::{ParseError, ParseErrorKind};
+use crate::scanner::{LineEnding, Operator, Scanner, Token, TokenKind, SCANNED_STRING_BUFFER_SIZE};
+use core::convert::TryFrom;
+
+/// The deepest nesting of parentheses and unary minus allowed in an expression, which bounds the
+/// recursion.
+const MAX_EXPRESSION_DEPTH: usize = 8;
+
+/// Reads tokens from a reader and keeps track of where each token of a line was found.
+///
+/// Each call to `get_token` starts a new argument, which is the position errors are reported
+/// against. `N` is the capacity of the scanner buffer.
+pub struct Lexer<R, const N: usize = SCANNED_STRING_BUFFER_SIZE> {
+    reader: R,
+    scanner: Scanner<N>,
+    tokens: usize,
+    line_finished: bool,
+    peeked: Option<Result<TokenKind, ParseErrorKind>>,
+    argument_line: usize,
+    argument_offset: usize,
+}
+
+impl<R> Lexer<R>
+where
+    R: crate::reader::Reader,
+{
+    pub fn new(reader: R) -> Lexer<R> {
+        Lexer::with_capacity(reader)
+    }
+}
+
+impl<R, const N: usize> Lexer<R, N>
+where
+    R: crate::reader::Reader,
+{
+    /// Creates a lexer whose scanner buffer holds `N` bytes.
+    pub fn with_capacity(reader: R) -> Lexer<R, N> {
+        Lexer {
+            reader,
+            scanner: Scanner::default(),
+            tokens: 0,
+            line_finished: true,
+            peeked: None,
+            argument_line: 0,
+            argument_offset: 0,
+        }
+    }
+
+    pub fn destroy(self: Lexer<R, N>) -> R {
+        self.reader
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.scanner.set_line_ending(line_ending);
+    }
+
+    /// Starts counting the tokens of a new line.
+    pub fn start(&mut self) {
+        self.tokens = 0;
+    }
+
+    /// Discards the input up to the end of the current line and resets the scanner.
+    ///
+    /// Does nothing if the last token read was the line terminator.
+    pub fn recover(&mut self) -> Result<(), ParseError> {
+        self.peeked = None;
+        if self.scanner.take_pending_kind() == Some(TokenKind::Finish) {
+            self.line_finished = true;
+        }
+
+        if self.line_finished {
+            self.scanner.reset();
+            return Ok(());
+        }
+
+        loop {
+            let c = match self.reader.read() {
+                Some(c) => c,
+                None => return Err(self.error(ParseErrorKind::EndOfInput)),
+            };
+
+            if self.scanner.skip_line(c) {
+                self.line_finished = true;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns an error pointing at the last token read.
+    pub fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            argument: self.tokens.saturating_sub(1),
+            line: self.scanner.token_line(),
+            offset: self.scanner.token_offset(),
+        }
+    }
+
+    /// Returns an error pointing at the start of the current argument rather than at its last
+    /// token, as for the value of an expression.
+    pub fn argument_error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.argument_line,
+            offset: self.argument_offset,
+            ..self.error(kind)
+        }
+    }
+
+    /// Reads the first token of the next argument.
+    ///
+    /// Invalid tokens are reported as errors, so `Token::Invalid` is never returned.
+    ///
+    /// The token borrows the lexer mutably. Use `get_kind` and `token` instead to query the lexer,
+    /// for example to report an error, while holding the token.
+    pub fn get_token(&mut self) -> Result<Token<'_>, ParseError> {
+        let kind = self.get_kind()?;
+        Ok(self.token(kind))
+    }
+
+    /// Reads the first token of the next argument like `get_token` but returns only its kind.
+    pub fn get_kind(&mut self) -> Result<TokenKind, ParseError> {
+        self.tokens += 1;
+        let kind = self.next_token()?;
+        self.argument_line = self.scanner.token_line();
+        self.argument_offset = self.scanner.token_offset();
+        Ok(kind)
+    }
+
+    /// Returns the token of the given kind with the value read last.
+    pub fn token(&self, kind: TokenKind) -> Token<'_> {
+        self.scanner.token(kind)
+    }
+
+    /// Reads the next token, keeping the error if there is one so that it is reported for the
+    /// argument which the token starts.
+    fn peek_token(&mut self) -> Result<TokenKind, ParseErrorKind> {
+        self.next_token().map_err(|e| e.kind)
+    }
+
+    /// Pushes back a token read by `peek_token` so that the next call to `get_token` returns it.
+    ///
+    /// The scanner keeps the value of the token as it has not scanned any further.
+    fn unget_token(&mut self, token: Result<TokenKind, ParseErrorKind>) {
+        self.peeked = Some(token);
+    }
+
+    /// Reads the next token of the current argument.
+    fn next_token(&mut self) -> Result<TokenKind, ParseError> {
+        if let Some(token) = self.peeked.take() {
+            return token.map_err(|kind| self.error(kind));
+        }
+
+        loop {
+            let res = match self.scanner.take_pending_kind() {
+                Some(token) => Some(token),
+                None => match self.reader.read() {
+                    Some(c) => self.scanner.scan(c),
+                    None => return Err(self.error(ParseErrorKind::EndOfInput)),
+                },
+            };
+            if let Some(token) = &res {
+                self.line_finished = *token == TokenKind::Finish;
+            }
+
+            match res {
+                Some(TokenKind::Invalid) if self.scanner.number_overflowed() => {
+                    return Err(self.error(ParseErrorKind::OutOfRange))
+                }
+                Some(TokenKind::Invalid) => return Err(self.error(ParseErrorKind::InvalidToken)),
+                Some(token) => return Ok(token),
+                None => (),
+            }
+        }
+    }
+
+    pub fn expect_number(&mut self) -> Result<i128, ParseError> {
+        match self.get_token()? {
+            Token::Number(value) => Ok(value),
+            _ => Err(self.error(ParseErrorKind::UnexpectedToken)),
+        }
+    }
+
+    /// Reads an integer expression and returns its value.
+    ///
+    /// The expression is made of numbers, including quoted single characters, the binary operators
+    /// `+ - * / << >> | &` with the precedence they have in C, unary minus and parentheses. Like the
+    /// numbers themselves, every intermediate value must have a magnitude which fits in a `u64`.
+    /// Overflow and division by zero are reported as `ParseErrorKind::OutOfRange`, and parentheses
+    /// or unary minus nested more than 8 deep as `ParseErrorKind::ExpressionTooDeep`.
+    pub fn expect_expression(&mut self) -> Result<i128, ParseError> {
+        let kind = self.get_kind()?;
+        self.parse_expression(kind)
+    }
+
+    /// Reads the rest of an expression like `expect_expression`, when its first token has already
+    /// been read with `get_kind`.
+    ///
+    /// This lets a dialect look at the first token of an argument, for example to accept either a
+    /// string or a number, before reading it as an expression.
+    pub fn parse_expression(&mut self, kind: TokenKind) -> Result<i128, ParseError> {
+        let lhs = self.parse_operand(kind, 0)?;
+        self.parse_binary(lhs, 0, 0)
+    }
+
+    /// Reads the range operator following the current argument as part of it, if there is one.
+    ///
+    /// Returns `TokenKind::Range`, `TokenKind::RangeInclusive` or `TokenKind::RangeLength`, after
+    /// which the end or the length of the range follows. Any other token is left to be read by the
+    /// next call to `get_token`.
+    pub fn take_range_operator(&mut self) -> Option<TokenKind> {
+        match self.peek_token() {
+            Ok(TokenKind::Range) => Some(TokenKind::Range),
+            Ok(TokenKind::RangeInclusive) => Some(TokenKind::RangeInclusive),
+            Ok(TokenKind::RangeLength) => Some(TokenKind::RangeLength),
+            token => {
+                self.unget_token(token);
+                None
+            }
+        }
+    }
+
+    fn parse_operand(&mut self, kind: TokenKind, depth: usize) -> Result<i128, ParseError> {
+        match self.token(kind) {
+            Token::Number(value) => Ok(value),
+            Token::LeftParen | Token::Operator(Operator::Subtract)
+                if depth >= MAX_EXPRESSION_DEPTH =>
+            {
+                Err(self.error(ParseErrorKind::ExpressionTooDeep))
+            }
+            Token::LeftParen => {
+                let kind = self.next_token()?;
+                let lhs = self.parse_operand(kind, depth + 1)?;
+                let value = self.parse_binary(lhs, 0, depth + 1)?;
+                if self.next_token()? != TokenKind::RightParen {
+                    Err(self.error(ParseErrorKind::UnexpectedToken))
+                } else {
+                    Ok(value)
+                }
+            }
+            Token::Operator(Operator::Subtract) => {
+                let kind = self.next_token()?;
+                let value = self.parse_operand(kind, depth + 1)?;
+                value
+                    .checked_neg()
+                    .ok_or_else(|| self.argument_error(ParseErrorKind::OutOfRange))
+            }
+            _ => Err(self.error(ParseErrorKind::UnexpectedToken)),
+        }
+    }
+
+    /// Applies the operators following `lhs` which bind at least as tightly as `precedence`.
+    fn parse_binary(
+        &mut self,
+        mut lhs: i128,
+        precedence: u8,
+        depth: usize,
+    ) -> Result<i128, ParseError> {
+        loop {
+            let token = self.peek_token();
+            let operator = match token.as_ref().map(|kind| self.token(*kind)) {
+                Ok(Token::Operator(operator)) if precedence_of(operator) >= precedence => operator,
+                _ => {
+                    self.unget_token(token);
+                    return Ok(lhs);
+                }
+            };
+
+            let kind = self.next_token()?;
+            let rhs = self.parse_operand(kind, depth)?;
+            let rhs = self.parse_binary(rhs, precedence_of(operator) + 1, depth)?;
+            lhs = match apply(operator, lhs, rhs) {
+                Some(value) => value,
+                None => return Err(self.argument_error(ParseErrorKind::OutOfRange)),
+            };
+        }
+    }
+
+    pub fn expect_finish(&mut self) -> Result<(), ParseError> {
+        if self.get_kind()? != TokenKind::Finish {
+            Err(self.error(ParseErrorKind::MissingTerminator))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn precedence_of(operator: Operator) -> u8 {
+    match operator {
+        Operator::Or => 1,
+        Operator::And => 2,
+        Operator::ShiftLeft | Operator::ShiftRight => 3,
+        Operator::Add | Operator::Subtract => 4,
+        Operator::Multiply | Operator::Divide => 5,
+    }
+}
+
+/// Returns the result of the operator, or `None` if it overflows or divides by zero.
+fn apply(operator: Operator, lhs: i128, rhs: i128) -> Option<i128> {
+    let value = match operator {
+        Operator::Add => lhs.checked_add(rhs),
+        Operator::Subtract => lhs.checked_sub(rhs),
+        Operator::Multiply => lhs.checked_mul(rhs),
+        Operator::Divide => lhs.checked_div(rhs),
+        Operator::ShiftLeft => {
+            let shift = u32::try_from(rhs).ok()?;
+            let value = lhs.checked_shl(shift)?;
+            // Bits shifted out are lost, so a left shift overflows if it cannot be undone
+            if value >> shift == lhs {
+                Some(value)
+            } else {
+                None
+            }
+        }
+        Operator::ShiftRight => lhs.checked_shr(u32::try_from(rhs).ok()?),
+        Operator::Or => Some(lhs | rhs),
+        Operator::And => Some(lhs & rhs),
+    }?;
+
+    if value.unsigned_abs() <= u64::MAX as u128 {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::Lexer;
+    use crate::parser::{ParseError, ParseErrorKind};
+    use crate::reader::StandardReader;
+    use crate::scanner::{Operator, Token, TokenKind};
+
+    #[test]
+    fn get_token_with_value() {
+        let reader = StandardReader::new("set speed -(2+3)*4 'on'\r\n".as_bytes());
+        let mut lexer = Lexer::new(reader);
+        lexer.start();
+
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"set")));
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"speed")));
+        assert_eq!(lexer.get_token(), Ok(Token::Operator(Operator::Subtract)));
+        assert_eq!(lexer.get_token(), Ok(Token::LeftParen));
+        assert_eq!(lexer.get_token(), Ok(Token::Number(2)));
+        assert_eq!(lexer.get_token(), Ok(Token::Operator(Operator::Add)));
+        assert_eq!(lexer.get_token(), Ok(Token::Number(3)));
+        assert_eq!(lexer.get_token(), Ok(Token::RightParen));
+        assert_eq!(lexer.get_token(), Ok(Token::Operator(Operator::Multiply)));
+        assert_eq!(lexer.get_token(), Ok(Token::Number(4)));
+        assert_eq!(lexer.get_token(), Ok(Token::String(b"on")));
+        assert_eq!(lexer.get_token(), Ok(Token::Finish));
+    }
+
+    #[test]
+    fn expect_expression_after_identifier() {
+        let reader = StandardReader::new("set speed -(2+3)*4\r\nset on\r\n".as_bytes());
+        let mut lexer = Lexer::new(reader);
+        lexer.start();
+
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"set")));
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"speed")));
+        assert_eq!(lexer.expect_expression(), Ok(-20));
+        assert_eq!(lexer.expect_finish(), Ok(()));
+
+        lexer.start();
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"set")));
+        assert_eq!(
+            lexer.expect_number(),
+            Err(ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                argument: 1,
+                line: 1,
+                offset: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn evaluate_64bit_expression() {
+        let reader = StandardReader::new(
+            "0xFFFF_FFFF_FFFF_FFFF >> 32\r\n0x8000_0000_0000_0000 * 2\r\n".as_bytes(),
+        );
+        let mut lexer = Lexer::new(reader);
+        lexer.start();
+
+        assert_eq!(lexer.expect_expression(), Ok(0xFFFF_FFFF));
+        assert_eq!(lexer.expect_finish(), Ok(()));
+
+        lexer.start();
+        assert_eq!(
+            lexer.expect_expression().map_err(|e| e.kind),
+            Err(ParseErrorKind::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn report_error_while_holding_token() {
+        let reader = StandardReader::new("set 'on'\r\n".as_bytes());
+        let mut lexer = Lexer::new(reader);
+        lexer.start();
+
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"set")));
+        let kind = lexer.get_kind().unwrap();
+        let token = lexer.token(kind);
+        let error = lexer.error(ParseErrorKind::UnexpectedToken);
+        assert_eq!(token, Token::String(b"on"));
+        assert_eq!((error.argument, error.line, error.offset), (1, 0, 4));
+    }
+
+    #[test]
+    fn read_quoted_character() {
+        let reader = StandardReader::new("set x 'y' 'A'+1\r\n".as_bytes());
+        let mut lexer = Lexer::new(reader);
+        lexer.start();
+
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"set")));
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"x")));
+        assert_eq!(lexer.expect_number(), Ok(0x79));
+        assert_eq!(lexer.expect_expression(), Ok(0x42));
+        assert_eq!(lexer.expect_finish(), Ok(()));
+    }
+
+    #[test]
+    fn parse_dialect_arguments() {
+        let reader = StandardReader::new("set_frequency 'A'+1 0x10..+4\r\n".as_bytes());
+        let mut lexer = Lexer::<_, 32>::with_capacity(reader);
+        lexer.start();
+
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"set_frequency")));
+        let kind = lexer.get_kind().unwrap();
+        assert_eq!(lexer.token(kind), Token::Number(0x41));
+        assert_eq!(lexer.parse_expression(kind), Ok(0x42));
+        assert_eq!(lexer.take_range_operator(), None);
+        assert_eq!(lexer.expect_expression(), Ok(0x10));
+        assert_eq!(lexer.take_range_operator(), Some(TokenKind::RangeLength));
+        assert_eq!(lexer.expect_expression(), Ok(4));
+        assert_eq!(lexer.expect_finish(), Ok(()));
+    }
+
+    #[test]
+    fn report_invalid_token() {
+        let reader = StandardReader::new("set 0x1_0000_0000_0000_0000\r\n".as_bytes());
+        let mut lexer = Lexer::new(reader);
+        lexer.start();
+
+        assert_eq!(lexer.get_token(), Ok(Token::Identifier(b"set")));
+        assert_eq!(
+            lexer.get_token().map_err(|e| e.kind),
+            Err(ParseErrorKind::OutOfRange)
+        );
+    }
+}
@@ -1,6 +1,12 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+pub mod encoder;
+pub mod lexer;
 pub mod parser;
+pub mod protocol;
 pub mod reader;
+pub mod response;
 pub mod scanner;
 pub mod util;
+
+pub use crate::protocol::PROTOCOL_VERSION;
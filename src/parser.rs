@@ -1,15 +1,136 @@
-use crate::scanner::Token;
+use crate::lexer::Lexer;
+use crate::scanner::{LineEnding, Token, TokenKind, SCANNED_STRING_BUFFER_SIZE};
+use crate::util::{u8_str_equal_ignore_case, ByteBuffer, BYTE_BUFFER_SIZE};
 
+#[cfg(feature = "display")]
+use core::fmt::Formatter;
+
+/// A command parsed by `Parser::new`, whose `WriteData` payload holds up to `BYTE_BUFFER_SIZE`
+/// bytes.
+pub type Command = GenericCommand<BYTE_BUFFER_SIZE>;
+
+/// A command parsed by `Parser`.
+///
+/// `N` is the capacity of the payload of `WriteData`, which is the capacity of the parser.
 #[derive(PartialEq, Debug)]
-pub enum Command {
+pub enum GenericCommand<const N: usize> {
     ReadByte(u32),
     WriteByte(u32, u8),
     ReadData(u32, u32),
     WritePage(u16),
     SetDevice(DeviceName),
+    WriteData(u32, ByteBuffer<N>),
+    Fill(u32, u32, u8),
+    /// Fills the whole selected device with 0xFF.
+    Erase,
+    /// Compares the CRC-32 of the address range with the expected value.
+    Verify(u32, u32, u32),
+    /// Queries the CRC-32 of the address range.
+    Crc(u32, u32),
+    /// Queries the capabilities of the programmer.
+    Identify,
+    /// Queries the protocol version of the programmer.
+    Version,
+    /// Queries the selected device, the last error and whether a write is in progress.
+    Status,
+    /// Scans the bus for responding devices.
+    Probe,
 }
 
-#[derive(PartialEq, Debug)]
+/// The kind of a `Command`, without its arguments.
+///
+/// The discriminant is the bit assigned to the command in `protocol::Capabilities`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CommandKind {
+    ReadByte = 0,
+    WriteByte = 1,
+    ReadData = 2,
+    WritePage = 3,
+    SetDevice = 4,
+    WriteData = 5,
+    Fill = 6,
+    Erase = 7,
+    Verify = 8,
+    Crc = 9,
+    Identify = 10,
+    Version = 11,
+    Status = 12,
+    Probe = 13,
+}
+
+/// The mnemonic and the long-form alias of each command.
+const COMMAND_NAMES: [(&str, &str, CommandKind); 14] = [
+    ("rb", "read_byte", CommandKind::ReadByte),
+    ("wb", "write_byte", CommandKind::WriteByte),
+    ("rd", "read", CommandKind::ReadData),
+    ("wp", "write_page", CommandKind::WritePage),
+    ("sd", "set_device", CommandKind::SetDevice),
+    ("wd", "write_data", CommandKind::WriteData),
+    ("fill", "fill", CommandKind::Fill),
+    ("erase", "erase", CommandKind::Erase),
+    ("verify", "verify", CommandKind::Verify),
+    ("crc", "checksum", CommandKind::Crc),
+    ("id", "identify", CommandKind::Identify),
+    ("ver", "version", CommandKind::Version),
+    ("st", "status", CommandKind::Status),
+    ("probe", "probe", CommandKind::Probe),
+];
+
+/// The length of the longest command name, which is the smallest capacity of a `Parser`.
+pub const LONGEST_COMMAND_NAME: usize = longest_command_name();
+
+const fn longest_command_name() -> usize {
+    let mut longest = 0;
+    let mut i = 0;
+    while i < COMMAND_NAMES.len() {
+        let (mnemonic, alias, _) = COMMAND_NAMES[i];
+        if mnemonic.len() > longest {
+            longest = mnemonic.len();
+        }
+        if alias.len() > longest {
+            longest = alias.len();
+        }
+        i += 1;
+    }
+
+    longest
+}
+
+impl CommandKind {
+    /// Looks up the command by its mnemonic or its long-form alias, ignoring case.
+    pub fn from_name(name: &[u8]) -> Option<CommandKind> {
+        COMMAND_NAMES
+            .iter()
+            .find(|(mnemonic, alias, _)| {
+                u8_str_equal_ignore_case(name, mnemonic.as_bytes())
+                    || u8_str_equal_ignore_case(name, alias.as_bytes())
+            })
+            .map(|(_, _, kind)| *kind)
+    }
+}
+
+impl<const N: usize> GenericCommand<N> {
+    pub fn kind(&self) -> CommandKind {
+        match self {
+            GenericCommand::ReadByte(..) => CommandKind::ReadByte,
+            GenericCommand::WriteByte(..) => CommandKind::WriteByte,
+            GenericCommand::ReadData(..) => CommandKind::ReadData,
+            GenericCommand::WritePage(..) => CommandKind::WritePage,
+            GenericCommand::SetDevice(..) => CommandKind::SetDevice,
+            GenericCommand::WriteData(..) => CommandKind::WriteData,
+            GenericCommand::Fill(..) => CommandKind::Fill,
+            GenericCommand::Erase => CommandKind::Erase,
+            GenericCommand::Verify(..) => CommandKind::Verify,
+            GenericCommand::Crc(..) => CommandKind::Crc,
+            GenericCommand::Identify => CommandKind::Identify,
+            GenericCommand::Version => CommandKind::Version,
+            GenericCommand::Status => CommandKind::Status,
+            GenericCommand::Probe => CommandKind::Probe,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum DeviceName {
     X00,
     X01,
@@ -26,9 +147,197 @@ pub enum DeviceName {
     XM02,
 }
 
-pub struct Parser<R> {
-    reader: R,
-    scanner: crate::scanner::Scanner,
+impl DeviceName {
+    /// Returns the name used for the device in the command text.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            DeviceName::X00 => "x00",
+            DeviceName::X01 => "x01",
+            DeviceName::X02 => "x02",
+            DeviceName::X04 => "x04",
+            DeviceName::X08 => "x08",
+            DeviceName::X16 => "x16",
+            DeviceName::X32 => "x32",
+            DeviceName::X64 => "x64",
+            DeviceName::X128 => "x128",
+            DeviceName::X256 => "x256",
+            DeviceName::X512 => "x512",
+            DeviceName::XM01 => "xm01",
+            DeviceName::XM02 => "xm02",
+        }
+    }
+
+    /// Looks up the device by its name in the command text.
+    ///
+    /// The name is compared ignoring case.
+    pub fn from_mnemonic(name: &[u8]) -> Option<DeviceName> {
+        if u8_str_equal_ignore_case(name, "x00".as_bytes()) {
+            Some(DeviceName::X00)
+        } else if u8_str_equal_ignore_case(name, "x01".as_bytes()) {
+            Some(DeviceName::X01)
+        } else if u8_str_equal_ignore_case(name, "x02".as_bytes()) {
+            Some(DeviceName::X02)
+        } else if u8_str_equal_ignore_case(name, "x04".as_bytes()) {
+            Some(DeviceName::X04)
+        } else if u8_str_equal_ignore_case(name, "x08".as_bytes()) {
+            Some(DeviceName::X08)
+        } else if u8_str_equal_ignore_case(name, "x16".as_bytes()) {
+            Some(DeviceName::X16)
+        } else if u8_str_equal_ignore_case(name, "x32".as_bytes()) {
+            Some(DeviceName::X32)
+        } else if u8_str_equal_ignore_case(name, "x64".as_bytes()) {
+            Some(DeviceName::X64)
+        } else if u8_str_equal_ignore_case(name, "x128".as_bytes()) {
+            Some(DeviceName::X128)
+        } else if u8_str_equal_ignore_case(name, "x256".as_bytes()) {
+            Some(DeviceName::X256)
+        } else if u8_str_equal_ignore_case(name, "x512".as_bytes()) {
+            Some(DeviceName::X512)
+        } else if u8_str_equal_ignore_case(name, "xm01".as_bytes()) {
+            Some(DeviceName::XM01)
+        } else if u8_str_equal_ignore_case(name, "xm02".as_bytes()) {
+            Some(DeviceName::XM02)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the capacity of the device in bytes.
+    pub fn capacity(&self) -> u32 {
+        match self {
+            DeviceName::X00 => 16,
+            DeviceName::X01 => 128,
+            DeviceName::X02 => 256,
+            DeviceName::X04 => 512,
+            DeviceName::X08 => 1024,
+            DeviceName::X16 => 2048,
+            DeviceName::X32 => 4096,
+            DeviceName::X64 => 8192,
+            DeviceName::X128 => 16384,
+            DeviceName::X256 => 32768,
+            DeviceName::X512 => 65536,
+            DeviceName::XM01 => 131072,
+            DeviceName::XM02 => 262144,
+        }
+    }
+
+    /// Returns the size of a write page in bytes.
+    ///
+    /// The 24C00 has no page write, so its pages are a single byte.
+    pub fn page_size(&self) -> u16 {
+        match self {
+            DeviceName::X00 => 1,
+            DeviceName::X01 | DeviceName::X02 => 8,
+            DeviceName::X04 | DeviceName::X08 | DeviceName::X16 => 16,
+            DeviceName::X32 | DeviceName::X64 => 32,
+            DeviceName::X128 | DeviceName::X256 => 64,
+            DeviceName::X512 => 128,
+            DeviceName::XM01 | DeviceName::XM02 => 256,
+        }
+    }
+
+    /// Returns the number of pages in the device.
+    pub fn page_count(&self) -> u32 {
+        self.capacity() / self.page_size() as u32
+    }
+
+    /// Returns the number of address bytes sent after the device address.
+    pub fn address_bytes(&self) -> u8 {
+        match self {
+            DeviceName::X00
+            | DeviceName::X01
+            | DeviceName::X02
+            | DeviceName::X04
+            | DeviceName::X08
+            | DeviceName::X16 => 1,
+            _ => 2,
+        }
+    }
+
+    /// Returns the number of the I2C device address bits which select a block of the memory.
+    ///
+    /// These bits hold the upper address bits, e.g. A8-A10 on the 24C16 and A16-A17 on the 24M02.
+    pub fn block_select_bits(&self) -> u8 {
+        match self {
+            DeviceName::X04 | DeviceName::XM01 => 1,
+            DeviceName::X08 | DeviceName::XM02 => 2,
+            DeviceName::X16 => 3,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ParseErrorKind {
+    UnknownCommand,
+    UnknownDevice,
+    UnknownResponse,
+    UnexpectedToken,
+    OutOfRange,
+    MissingTerminator,
+    InvalidToken,
+    EndOfInput,
+    ExceedsDevice,
+    /// An expression nests parentheses or unary minus too deeply.
+    ExpressionTooDeep,
+}
+
+impl ParseErrorKind {
+    /// Returns the error code reported to the host in `response::ErrorReport`.
+    pub fn code(&self) -> u8 {
+        match self {
+            ParseErrorKind::UnknownCommand => 1,
+            ParseErrorKind::UnknownDevice => 2,
+            ParseErrorKind::UnknownResponse => 3,
+            ParseErrorKind::UnexpectedToken => 4,
+            ParseErrorKind::OutOfRange => 5,
+            ParseErrorKind::MissingTerminator => 6,
+            ParseErrorKind::InvalidToken => 7,
+            ParseErrorKind::EndOfInput => 8,
+            ParseErrorKind::ExceedsDevice => 9,
+            ParseErrorKind::ExpressionTooDeep => 10,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<ParseErrorKind> {
+        match code {
+            1 => Some(ParseErrorKind::UnknownCommand),
+            2 => Some(ParseErrorKind::UnknownDevice),
+            3 => Some(ParseErrorKind::UnknownResponse),
+            4 => Some(ParseErrorKind::UnexpectedToken),
+            5 => Some(ParseErrorKind::OutOfRange),
+            6 => Some(ParseErrorKind::MissingTerminator),
+            7 => Some(ParseErrorKind::InvalidToken),
+            8 => Some(ParseErrorKind::EndOfInput),
+            9 => Some(ParseErrorKind::ExceedsDevice),
+            10 => Some(ParseErrorKind::ExpressionTooDeep),
+            _ => None,
+        }
+    }
+}
+
+/// The error returned when a command could not be parsed.
+///
+/// `argument` is the index of the token that failed within the command, where 0 is the command
+/// name itself and 1 is its first argument. `line` and `offset` locate that token in the input,
+/// both counted from zero.
+#[derive(PartialEq, Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub argument: usize,
+    pub line: usize,
+    pub offset: usize,
+}
+
+/// The parser for the commands read from `R`.
+///
+/// `N` is the capacity of the scanner buffer, which limits the length of identifiers, of quoted
+/// strings and of the payload of `wd`. It must be at least `LONGEST_COMMAND_NAME`.
+pub struct Parser<R, const N: usize = SCANNED_STRING_BUFFER_SIZE> {
+    lexer: Lexer<R, N>,
+    recovery: bool,
+    validation: bool,
+    device: Option<DeviceName>,
 }
 
 impl<R> Parser<R>
@@ -36,206 +345,396 @@ where
     R: crate::reader::Reader,
 {
     pub fn new(reader: R) -> Parser<R> {
+        Parser::with_capacity(reader)
+    }
+}
+
+impl<R, const N: usize> Parser<R, N>
+where
+    R: crate::reader::Reader,
+{
+    /// Fails to compile when the scanner buffer cannot hold every command name.
+    const FITS_COMMAND_NAMES: () = assert!(
+        N >= LONGEST_COMMAND_NAME,
+        "the capacity of a parser must be at least LONGEST_COMMAND_NAME"
+    );
+
+    /// Creates a parser whose scanner buffer holds `N` bytes.
+    ///
+    /// `N` must be at least `LONGEST_COMMAND_NAME`, which is checked at compile time.
+    pub fn with_capacity(reader: R) -> Parser<R, N> {
+        let () = Self::FITS_COMMAND_NAMES;
         Parser {
-            reader,
-            scanner: crate::scanner::Scanner::default(),
+            lexer: Lexer::with_capacity(reader),
+            recovery: false,
+            validation: false,
+            device: None,
         }
     }
 
-    fn get_token(&mut self) -> Result<Token, ()> {
-        loop {
-            let c = self.reader.read();
-            if c.is_none() {
-                return Err(());
-            }
+    /// Enables or disables the validation against the selected device.
+    ///
+    /// When enabled, commands whose address, length or page do not fit in the device selected by
+    /// the last `sd` command are rejected with `ParseErrorKind::ExceedsDevice`. Commands are not
+    /// checked until a device is selected.
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.validation = enabled;
+    }
 
-            let res = self.scanner.scan_command(c.unwrap());
-            if res.is_some() {
-                return Ok(res.unwrap());
-            }
+    /// Returns the device selected by the last successfully parsed `sd` command.
+    pub fn device(&self) -> Option<DeviceName> {
+        self.device
+    }
+
+    /// Enables or disables the recovery mode.
+    ///
+    /// When enabled, `parse_command` discards the rest of the offending line after an error so
+    /// that the next call starts at the beginning of a new command.
+    pub fn set_recovery(&mut self, enabled: bool) {
+        self.recovery = enabled;
+    }
+
+    /// Resynchronizes to the next line like `Lexer::recover`.
+    pub fn recover(&mut self) -> Result<(), ParseError> {
+        self.lexer.recover()
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.lexer.set_line_ending(line_ending);
+    }
+
+    pub fn destroy(self: Parser<R, N>) -> R {
+        self.lexer.destroy()
+    }
+
+    pub fn parse_command(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        self.lexer.start();
+        let res = self.dispatch_command();
+        if res.is_err() && self.recovery {
+            // The original error is more useful to the caller than a failed recovery. If the input
+            // ran out, the next call reports that instead.
+            let _ = self.recover();
+        }
+
+        res
+    }
+
+    fn dispatch_command(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        let name = match self.lexer.get_token()? {
+            Token::Identifier(name) => name,
+            _ => return Err(self.lexer.error(ParseErrorKind::UnexpectedToken)),
+        };
+
+        match CommandKind::from_name(name) {
+            Some(CommandKind::ReadByte) => self.parse_read_byte(),
+            Some(CommandKind::WriteByte) => self.parse_write_byte(),
+            Some(CommandKind::ReadData) => self.parse_read_data(),
+            Some(CommandKind::WritePage) => self.parse_write_page(),
+            Some(CommandKind::SetDevice) => self.parse_set_device(),
+            Some(CommandKind::WriteData) => self.parse_write_data(),
+            Some(CommandKind::Fill) => self.parse_fill(),
+            Some(CommandKind::Erase) => self.parse_erase(),
+            Some(CommandKind::Verify) => self.parse_verify(),
+            Some(CommandKind::Crc) => self.parse_crc(),
+            Some(CommandKind::Identify) => self.parse_identify(),
+            Some(CommandKind::Version) => self.parse_version(),
+            Some(CommandKind::Status) => self.parse_status(),
+            Some(CommandKind::Probe) => self.parse_probe(),
+            None => Err(self.lexer.error(ParseErrorKind::UnknownCommand)),
         }
     }
 
-    pub fn destroy(self: Parser<R>) -> R {
-        self.reader
+    fn parse_address(&mut self) -> Result<u32, ParseError> {
+        let addr = self.lexer.expect_expression()?;
+        self.to_u32(addr)
+    }
+
+    fn parse_data(&mut self) -> Result<u8, ParseError> {
+        let data = self.lexer.expect_expression()?;
+        self.to_byte(data)
     }
 
-    pub fn parse_command(&mut self) -> Result<Command, ()> {
-        let cmd = self.get_token()?;
-        if cmd != Token::Identifier {
-            Err(())
+    fn to_u32(&self, value: i128) -> Result<u32, ParseError> {
+        if !(0..=u32::MAX as i128).contains(&value) {
+            Err(self.lexer.argument_error(ParseErrorKind::OutOfRange))
         } else {
-            let cmd_str = self.scanner.scanned_string;
-            if crate::util::u8_str_equal(&cmd_str, "rb\0".as_bytes()) {
-                self.parse_read_byte()
-            } else if crate::util::u8_str_equal(&cmd_str, "wb\0".as_bytes()) {
-                self.parse_write_byte()
-            } else if crate::util::u8_str_equal(&cmd_str, "rd\0".as_bytes()) {
-                self.parse_read_data()
-            } else if crate::util::u8_str_equal(&cmd_str, "wp\0".as_bytes()) {
-                self.parse_write_page()
-            } else if crate::util::u8_str_equal(&cmd_str, "sd\0".as_bytes()) {
-                self.parse_set_device()
+            Ok(value as u32)
+        }
+    }
+
+    fn to_byte(&self, data: i128) -> Result<u8, ParseError> {
+        if data < 0 {
+            if data >= -128 {
+                Ok((0x100 + data) as u8)
             } else {
-                Err(())
+                Err(self.lexer.argument_error(ParseErrorKind::OutOfRange))
             }
+        } else if data < 256 {
+            Ok(data as u8)
+        } else {
+            Err(self.lexer.argument_error(ParseErrorKind::OutOfRange))
         }
     }
 
-    fn parse_address(&mut self) -> Result<u32, ()> {
-        let arg = self.get_token()?;
-        if arg != Token::Number {
-            Err(())
+    fn parse_length(&mut self) -> Result<u32, ParseError> {
+        let len = self.lexer.expect_expression()?;
+        self.to_u32(len)
+    }
+
+    fn parse_page(&mut self) -> Result<u16, ParseError> {
+        let page = self.lexer.expect_expression()?;
+        if !(0..=1023).contains(&page) {
+            Err(self.lexer.argument_error(ParseErrorKind::OutOfRange))
         } else {
-            let addr = self.scanner.scanned_number;
-            if addr < 0 {
-                Err(())
-            } else {
-                Ok(addr as u32)
-            }
+            Ok(page as u16)
         }
     }
 
-    fn parse_data(&mut self) -> Result<u8, ()> {
-        let arg = self.get_token()?;
-        if arg != Token::Number {
-            Err(())
+    fn parse_checksum(&mut self) -> Result<u32, ParseError> {
+        let crc = self.lexer.expect_expression()?;
+        self.to_checksum(crc)
+    }
+
+    fn to_checksum(&self, crc: i128) -> Result<u32, ParseError> {
+        // Negative checksums are accepted as their 32-bit two's complement counterparts
+        if !(i32::MIN as i128..=u32::MAX as i128).contains(&crc) {
+            Err(self.lexer.argument_error(ParseErrorKind::OutOfRange))
         } else {
-            let data = self.scanner.scanned_number;
-            if data < 0 {
-                if data >= -128 {
-                    Ok((0x100 + data) as u8)
-                } else {
-                    Err(())
-                }
-            } else {
-                if data < 256 {
-                    Ok(data as u8)
-                } else {
-                    Err(())
+            Ok(crc as u32)
+        }
+    }
+
+    /// Reads the range of bytes accessed by a command as its address and length.
+    ///
+    /// The range is given as an address and a length, as `start..end`, as `start..=end` or as
+    /// `start..+len`. A plain `+` is an addition, so `0x10+32` is the address 0x30.
+    fn parse_range(&mut self) -> Result<(u32, u32), ParseError> {
+        let addr = self.parse_address()?;
+        self.check_address(addr)?;
+        let len = match self.lexer.take_range_operator() {
+            Some(TokenKind::RangeLength) => self.parse_length()?,
+            Some(operator) => {
+                let end = self.parse_address()?;
+                let len = match operator {
+                    TokenKind::RangeInclusive => {
+                        end.checked_sub(addr).and_then(|len| len.checked_add(1))
+                    }
+                    _ => end.checked_sub(addr),
+                };
+                match len {
+                    Some(len) => len,
+                    None => return Err(self.lexer.argument_error(ParseErrorKind::OutOfRange)),
                 }
             }
+            None => self.parse_length()?,
+        };
+        self.check_length(addr, len)?;
+        Ok((addr, len))
+    }
+
+    fn parse_device_name(&mut self) -> Result<DeviceName, ParseError> {
+        let name = match self.lexer.get_token()? {
+            Token::Identifier(name) => name,
+            _ => return Err(self.lexer.error(ParseErrorKind::UnexpectedToken)),
+        };
+
+        match DeviceName::from_mnemonic(name) {
+            Some(device_name) => Ok(device_name),
+            None => Err(self.lexer.error(ParseErrorKind::UnknownDevice)),
         }
     }
 
-    fn parse_length(&mut self) -> Result<u32, ()> {
-        let arg = self.get_token()?;
-        if arg != Token::Number {
-            Err(())
+    fn validated_device(&self) -> Option<DeviceName> {
+        if self.validation {
+            self.device
         } else {
-            let addr = self.scanner.scanned_number;
-            if addr < 0 {
-                Err(())
-            } else {
-                Ok(addr as u32)
+            None
+        }
+    }
+
+    fn check_address(&self, addr: u32) -> Result<(), ParseError> {
+        match self.validated_device() {
+            Some(device) if addr >= device.capacity() => {
+                Err(self.lexer.argument_error(ParseErrorKind::ExceedsDevice))
             }
+            _ => Ok(()),
         }
     }
 
-    fn parse_page(&mut self) -> Result<u16, ()> {
-        let arg = self.get_token()?;
-        if arg != Token::Number {
-            Err(())
-        } else {
-            let page = self.scanner.scanned_number;
-            if page < 0 || 1023 < page {
-                Err(())
-            } else {
-                Ok(page as u16)
+    fn check_length(&self, addr: u32, len: u32) -> Result<(), ParseError> {
+        match self.validated_device() {
+            Some(device) if len > device.capacity() - addr => {
+                Err(self.lexer.argument_error(ParseErrorKind::ExceedsDevice))
             }
+            _ => Ok(()),
         }
     }
 
-    fn parse_device_name(&mut self) -> Result<DeviceName, ()> {
-        let arg = self.get_token()?;
-        if arg != Token::Identifier {
-            Err(())
-        } else {
-            let device_name_str = self.scanner.scanned_string;
-            if crate::util::u8_str_equal(&device_name_str, "x00\0".as_bytes()) {
-                Ok(DeviceName::X00)
-            } else if crate::util::u8_str_equal(&device_name_str, "x01\0".as_bytes()) {
-                Ok(DeviceName::X01)
-            } else if crate::util::u8_str_equal(&device_name_str, "x02\0".as_bytes()) {
-                Ok(DeviceName::X02)
-            } else if crate::util::u8_str_equal(&device_name_str, "x04\0".as_bytes()) {
-                Ok(DeviceName::X04)
-            } else if crate::util::u8_str_equal(&device_name_str, "x08\0".as_bytes()) {
-                Ok(DeviceName::X08)
-            } else if crate::util::u8_str_equal(&device_name_str, "x16\0".as_bytes()) {
-                Ok(DeviceName::X16)
-            } else if crate::util::u8_str_equal(&device_name_str, "x32\0".as_bytes()) {
-                Ok(DeviceName::X32)
-            } else if crate::util::u8_str_equal(&device_name_str, "x64\0".as_bytes()) {
-                Ok(DeviceName::X64)
-            } else if crate::util::u8_str_equal(&device_name_str, "x128\0".as_bytes()) {
-                Ok(DeviceName::X128)
-            } else if crate::util::u8_str_equal(&device_name_str, "x256\0".as_bytes()) {
-                Ok(DeviceName::X256)
-            } else if crate::util::u8_str_equal(&device_name_str, "x512\0".as_bytes()) {
-                Ok(DeviceName::X512)
-            } else if crate::util::u8_str_equal(&device_name_str, "xm01\0".as_bytes()) {
-                Ok(DeviceName::XM01)
-            } else if crate::util::u8_str_equal(&device_name_str, "xm02\0".as_bytes()) {
-                Ok(DeviceName::XM02)
-            } else {
-                Err(())
+    fn check_page(&self, page: u16) -> Result<(), ParseError> {
+        match self.validated_device() {
+            Some(device) if page as u32 >= device.page_count() => {
+                Err(self.lexer.argument_error(ParseErrorKind::ExceedsDevice))
             }
+            _ => Ok(()),
         }
     }
 
-    fn parse_read_byte(&mut self) -> Result<Command, ()> {
+    fn parse_read_byte(&mut self) -> Result<GenericCommand<N>, ParseError> {
         let addr = self.parse_address()?;
-        if self.get_token()? == Token::Finish {
-            Ok(Command::ReadByte(addr))
-        } else {
-            Err(())
-        }
+        self.check_address(addr)?;
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::ReadByte(addr))
     }
 
-    fn parse_write_byte(&mut self) -> Result<Command, ()> {
+    fn parse_write_byte(&mut self) -> Result<GenericCommand<N>, ParseError> {
         let addr = self.parse_address()?;
+        self.check_address(addr)?;
         let data = self.parse_data()?;
-        if self.get_token()? == Token::Finish {
-            Ok(Command::WriteByte(addr, data))
-        } else {
-            Err(())
-        }
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::WriteByte(addr, data))
+    }
+
+    fn parse_read_data(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        let (addr, len) = self.parse_range()?;
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::ReadData(addr, len))
+    }
+
+    fn parse_write_page(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        let page = self.parse_page()?;
+        self.check_page(page)?;
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::WritePage(page))
     }
 
-    fn parse_read_data(&mut self) -> Result<Command, ()> {
+    fn parse_write_data(&mut self) -> Result<GenericCommand<N>, ParseError> {
         let addr = self.parse_address()?;
-        let len = self.parse_length()?;
-        if self.get_token()? == Token::Finish {
-            Ok(Command::ReadData(addr, len))
-        } else {
-            Err(())
+        self.check_address(addr)?;
+        let mut payload = ByteBuffer::default();
+        loop {
+            let kind = self.lexer.get_kind()?;
+            match self.lexer.token(kind) {
+                Token::String(bytes) | Token::HexString(bytes) => {
+                    for c in bytes {
+                        self.push_payload(addr, &mut payload, *c)?;
+                    }
+                }
+                Token::Finish if !payload.is_empty() => {
+                    return Ok(GenericCommand::WriteData(addr, payload))
+                }
+                _ => {
+                    let data = self.lexer.parse_expression(kind)?;
+                    let data = self.to_byte(data)?;
+                    self.push_payload(addr, &mut payload, data)?;
+                }
+            }
         }
     }
 
-    fn parse_write_page(&mut self) -> Result<Command, ()> {
-        let page = self.parse_page()?;
-        if self.get_token()? == Token::Finish {
-            Ok(Command::WritePage(page))
-        } else {
-            Err(())
+    fn push_payload(
+        &self,
+        addr: u32,
+        payload: &mut ByteBuffer<N>,
+        data: u8,
+    ) -> Result<(), ParseError> {
+        if payload.push(data).is_err() {
+            return Err(self.lexer.argument_error(ParseErrorKind::OutOfRange));
         }
+
+        self.check_length(addr, payload.len() as u32)
+    }
+
+    fn parse_fill(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        let (addr, len) = self.parse_range()?;
+        let data = self.parse_data()?;
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Fill(addr, len, data))
+    }
+
+    fn parse_erase(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Erase)
+    }
+
+    fn parse_verify(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        let (addr, len) = self.parse_range()?;
+        let crc = self.parse_checksum()?;
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Verify(addr, len, crc))
+    }
+
+    fn parse_crc(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        let (addr, len) = self.parse_range()?;
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Crc(addr, len))
+    }
+
+    fn parse_identify(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Identify)
+    }
+
+    fn parse_version(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Version)
+    }
+
+    fn parse_status(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Status)
+    }
+
+    fn parse_probe(&mut self) -> Result<GenericCommand<N>, ParseError> {
+        self.lexer.expect_finish()?;
+        Ok(GenericCommand::Probe)
     }
 
-    fn parse_set_device(&mut self) -> Result<Command, ()> {
+    fn parse_set_device(&mut self) -> Result<GenericCommand<N>, ParseError> {
         let device_name = self.parse_device_name()?;
-        if self.get_token()? == Token::Finish {
-            Ok(Command::SetDevice(device_name))
-        } else {
-            Err(())
+        self.lexer.expect_finish()?;
+        self.device = Some(device_name);
+        Ok(GenericCommand::SetDevice(device_name))
+    }
+}
+
+#[cfg(feature = "display")]
+impl core::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseErrorKind::UnknownCommand => write!(f, "unknown command"),
+            ParseErrorKind::UnknownDevice => write!(f, "unknown device"),
+            ParseErrorKind::UnknownResponse => write!(f, "unknown response"),
+            ParseErrorKind::UnexpectedToken => write!(f, "unexpected token"),
+            ParseErrorKind::OutOfRange => write!(f, "value out of range"),
+            ParseErrorKind::MissingTerminator => write!(f, "missing terminator"),
+            ParseErrorKind::InvalidToken => write!(f, "invalid token"),
+            ParseErrorKind::EndOfInput => write!(f, "end of input"),
+            ParseErrorKind::ExceedsDevice => write!(f, "exceeds the selected device"),
+            ParseErrorKind::ExpressionTooDeep => write!(f, "expression nested too deeply"),
         }
     }
 }
 
+#[cfg(feature = "display")]
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "line {} offset {} argument {}: {}",
+            self.line, self.offset, self.argument, self.kind
+        )
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::parser::{Command, DeviceName, Parser};
+    use crate::parser::{
+        Command, CommandKind, DeviceName, GenericCommand, ParseError, ParseErrorKind, Parser,
+        COMMAND_NAMES, LONGEST_COMMAND_NAME,
+    };
     use crate::reader::StandardReader;
+    use crate::scanner::LineEnding;
+    use crate::util::ByteBuffer;
 
     #[test]
     fn parse_read_byte() {
@@ -291,4 +790,785 @@ mod test {
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), Command::SetDevice(DeviceName::XM01));
     }
+
+    fn expect_error(command: &str, kind: ParseErrorKind, argument: usize) {
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        let err = parser.parse_command().unwrap_err();
+
+        assert_eq!(err.kind, kind);
+        assert_eq!(err.argument, argument);
+    }
+
+    #[test]
+    fn parse_unknown_command() {
+        expect_error("xx 0x10\r\n", ParseErrorKind::UnknownCommand, 0);
+    }
+
+    #[test]
+    fn parse_unknown_device() {
+        expect_error("sd x03\r\n", ParseErrorKind::UnknownDevice, 1);
+    }
+
+    #[test]
+    fn parse_unexpected_token() {
+        expect_error("wb 0x10 'ab'\r\n", ParseErrorKind::UnexpectedToken, 2);
+    }
+
+    #[test]
+    fn parse_out_of_range() {
+        expect_error("wp 1024\r\n", ParseErrorKind::OutOfRange, 1);
+        expect_error("wb 0x10 256\r\n", ParseErrorKind::OutOfRange, 2);
+        expect_error("rb 0x100000000\r\n", ParseErrorKind::OutOfRange, 1);
+        expect_error("rd 0x10 -1\r\n", ParseErrorKind::OutOfRange, 2);
+        expect_error("verify 0 1 0x100000000\r\n", ParseErrorKind::OutOfRange, 3);
+        expect_error(
+            "rd 0x10 0x10000000000000000\r\n",
+            ParseErrorKind::OutOfRange,
+            2,
+        );
+    }
+
+    #[test]
+    fn parse_missing_terminator() {
+        expect_error("rb 0x10 0x20\r\n", ParseErrorKind::MissingTerminator, 2);
+    }
+
+    #[test]
+    fn parse_invalid_token() {
+        expect_error("rd 0x10 zz!\r\n", ParseErrorKind::InvalidToken, 2);
+    }
+
+    #[test]
+    fn parse_end_of_input() {
+        expect_error("rd 0x10 ", ParseErrorKind::EndOfInput, 2);
+    }
+
+    #[test]
+    fn report_error_position() {
+        let command = "rb 0x10\r\nwb 0x00012000 zz!\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert!(parser.parse_command().is_ok());
+        assert_eq!(
+            parser.parse_command(),
+            Err(ParseError {
+                kind: ParseErrorKind::InvalidToken,
+                argument: 2,
+                line: 1,
+                offset: 14,
+            })
+        );
+    }
+
+    #[test]
+    fn report_error_position_at_line_end() {
+        let command = "rb\r\nrb 1\r\nwb 2\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Err(ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                argument: 1,
+                line: 0,
+                offset: 2,
+            })
+        );
+        assert!(parser.parse_command().is_ok());
+        assert_eq!(
+            parser.parse_command(),
+            Err(ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                argument: 2,
+                line: 2,
+                offset: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_expressions() {
+        let command = "rd 0x100 + 3*32 2*(4+4)\r\nwb 1<<4|1 -1\r\nwp 1K/8 - 1\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::ReadData(0x160, 16)));
+        assert_eq!(parser.parse_command(), Ok(Command::WriteByte(0x11, 0xFF)));
+        assert_eq!(parser.parse_command(), Ok(Command::WritePage(127)));
+    }
+
+    #[test]
+    fn parse_expression_precedence() {
+        let command =
+            "rb 1+2*3\r\nrb (1+2)*3\r\nrb 10-4-3\r\nrb 1|6&3\r\nrb 1+1<<2\r\nrb -(2-5)\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        for expected in [7, 9, 3, 3, 8, 3].iter() {
+            assert_eq!(parser.parse_command(), Ok(Command::ReadByte(*expected)));
+        }
+    }
+
+    #[test]
+    fn parse_write_data_with_expressions() {
+        let command = "wd 0x10 0x40+1 'b' -1 (2 * 3)\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::WriteData(
+                0x10,
+                ByteBuffer::try_from_slice(&[0x41, b'b', 0xFF, 6]).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_write_data_with_escapes() {
+        let command = "wd 0x10 'SN\\x00\\x12\\r\\n'\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::WriteData(
+                0x10,
+                ByteBuffer::try_from_slice(b"SN\0\x12\r\n").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_character_literals() {
+        let command = "wb 0x10 'A'\r\nfill 0 4 ' '\r\nwb 0x10 'a'-0x20\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::WriteByte(0x10, 0x41)));
+        assert_eq!(parser.parse_command(), Ok(Command::Fill(0, 4, 0x20)));
+        assert_eq!(parser.parse_command(), Ok(Command::WriteByte(0x10, 0x41)));
+    }
+
+    #[test]
+    fn parse_write_data_with_hex_string() {
+        let command = "wd 0x10 h\"DEADBEEF\" 'A' 0x00 'a'-0x20 h\"41\"\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::WriteData(
+                0x10,
+                ByteBuffer::try_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF, 0x41, 0x00, 0x41, 0x41])
+                    .unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_expressions() {
+        expect_error("rb 0x10 + \r\n", ParseErrorKind::UnexpectedToken, 1);
+        expect_error("rb (1+2\r\n", ParseErrorKind::UnexpectedToken, 1);
+        expect_error("rb 1+2)\r\n", ParseErrorKind::MissingTerminator, 2);
+        expect_error("rd 0x10 zz!+1\r\n", ParseErrorKind::InvalidToken, 2);
+    }
+
+    #[test]
+    fn parse_deeply_nested_expressions() {
+        let command = "rb ((((((((1))))))))\r\nrb - - - - - - - - 1\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(1)));
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(1)));
+
+        expect_error(
+            "rb (((((((((1)))))))))\r\n",
+            ParseErrorKind::ExpressionTooDeep,
+            1,
+        );
+        expect_error(
+            "rb - - - - - - - - - 1\r\n",
+            ParseErrorKind::ExpressionTooDeep,
+            1,
+        );
+        assert_eq!(
+            ParseErrorKind::from_code(ParseErrorKind::ExpressionTooDeep.code()),
+            Some(ParseErrorKind::ExpressionTooDeep)
+        );
+    }
+
+    #[test]
+    fn parse_overflowing_expressions() {
+        let command =
+            "rd 0x10 0xFFFF_FFFF_FFFF_FFFF + 1\r\nrb 1/0\r\nrb 1<<64\r\nrb 1<<-1\r\nrb 1K*256K\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_recovery(true);
+
+        assert_eq!(
+            parser.parse_command(),
+            Err(ParseError {
+                kind: ParseErrorKind::OutOfRange,
+                argument: 2,
+                line: 0,
+                offset: 8,
+            })
+        );
+        for _ in 0..3 {
+            assert_eq!(
+                parser.parse_command().unwrap_err().kind,
+                ParseErrorKind::OutOfRange
+            );
+        }
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x1000_0000)));
+    }
+
+    #[test]
+    fn recover_after_bad_argument() {
+        let command = "wb 0x10 zz\r\nrb 0x20\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_recovery(true);
+
+        assert!(parser.parse_command().is_err());
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x20)));
+    }
+
+    #[test]
+    fn recover_after_extra_argument() {
+        let command = "rb 0x10 0x11\r\nrb 0x20\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_recovery(true);
+
+        assert!(parser.parse_command().is_err());
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x20)));
+    }
+
+    #[test]
+    fn recover_at_line_end() {
+        let command = "rb\r\nrb 0x20\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_recovery(true);
+
+        assert!(parser.parse_command().is_err());
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x20)));
+    }
+
+    #[test]
+    fn recover_at_invalid_line_end() {
+        let command = "wd 0x10 'abc\r\nrb 0x20\r\nwb 0x10 -\r\nrb 0x30\r\nrb 1\r\r\nrb 0x40\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_recovery(true);
+
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::InvalidToken
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x20)));
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::InvalidToken
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x30)));
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::InvalidToken
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x40)));
+    }
+
+    #[test]
+    fn recover_manually() {
+        let command = "wb 0x10 !!\r\nrb 0x20\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert!(parser.parse_command().is_err());
+        assert!(parser.recover().is_ok());
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x20)));
+    }
+
+    #[test]
+    fn device_geometry() {
+        let devices = [
+            DeviceName::X00,
+            DeviceName::X01,
+            DeviceName::X02,
+            DeviceName::X04,
+            DeviceName::X08,
+            DeviceName::X16,
+            DeviceName::X32,
+            DeviceName::X64,
+            DeviceName::X128,
+            DeviceName::X256,
+            DeviceName::X512,
+            DeviceName::XM01,
+            DeviceName::XM02,
+        ];
+
+        for device in devices.iter() {
+            let address_bits =
+                8 * device.address_bytes() as u32 + device.block_select_bits() as u32;
+            assert!(device.capacity() <= 1 << address_bits);
+            if device.block_select_bits() > 0 {
+                assert_eq!(device.capacity(), 1 << address_bits);
+            }
+            assert_eq!(device.capacity() % device.page_size() as u32, 0);
+        }
+
+        assert_eq!(DeviceName::X16.page_count(), 128);
+        assert_eq!(DeviceName::XM02.page_count(), 1024);
+    }
+
+    #[test]
+    fn validate_against_device() {
+        let command = "rb 0x80\r\nsd x01\r\nrb 0x7F\r\nrb 0x80\r\nrd 0x70 16\r\nrd 0x70 17\r\nwp 15\r\nwp 16\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_validation(true);
+
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x80)));
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::SetDevice(DeviceName::X01))
+        );
+        assert_eq!(parser.device(), Some(DeviceName::X01));
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x7F)));
+        assert_eq!(
+            parser.parse_command(),
+            Err(ParseError {
+                kind: ParseErrorKind::ExceedsDevice,
+                argument: 1,
+                line: 3,
+                offset: 3,
+            })
+        );
+        assert!(parser.recover().is_ok());
+        assert_eq!(parser.parse_command(), Ok(Command::ReadData(0x70, 16)));
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::ExceedsDevice
+        );
+        assert!(parser.recover().is_ok());
+        assert_eq!(parser.parse_command(), Ok(Command::WritePage(15)));
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::ExceedsDevice
+        );
+    }
+
+    #[test]
+    fn skip_validation_when_disabled() {
+        let command = "sd x01\r\nrb 0x000E3B41\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert!(parser.parse_command().is_ok());
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x000E3B41)));
+    }
+
+    #[test]
+    fn parse_write_data() {
+        let command = "wd 0x00000010 0x01 'AB' -1\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        let res = parser.parse_command();
+
+        let payload = ByteBuffer::try_from_slice(&[0x01, b'A', b'B', 0xFF]).unwrap();
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Command::WriteData(0x00000010, payload));
+    }
+
+    #[test]
+    fn parse_fill() {
+        let command = "fill 0x00000100 256 0xFF\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        let res = parser.parse_command();
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Command::Fill(0x00000100, 256, 0xFF));
+    }
+
+    #[test]
+    fn parse_erase() {
+        let command = "erase\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        let res = parser.parse_command();
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Command::Erase);
+    }
+
+    #[test]
+    fn parse_verify() {
+        let command = "verify 0x00000000 9 0x1234ABCD\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        let res = parser.parse_command();
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Command::Verify(0x00000000, 9, 0x1234ABCD));
+    }
+
+    #[test]
+    fn parse_verify_negative_checksum() {
+        let command = "verify 0x00000000 9 -873187034\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        let res = parser.parse_command();
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Command::Verify(0x00000000, 9, 0xCBF43926));
+    }
+
+    #[test]
+    fn parse_full_32bit_range() {
+        let command = "rd 0xFFFFFFFF 4294967295\r\nverify 0 9 0xCBF43926\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::ReadData(0xFFFFFFFF, 0xFFFFFFFF))
+        );
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::Verify(0, 9, 0xCBF43926))
+        );
+    }
+
+    #[test]
+    fn parse_number_with_separators_and_suffix() {
+        let command = "rd 0x0001_0000 2Ki\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::ReadData(0x10000, 2048)));
+    }
+
+    #[test]
+    fn parse_ranges() {
+        let command = "rd 0x10..0x30\r\nrd 0x10..=0x2F\r\nrd 0x10..+32\r\nrd 0x8+8..0x20+16\r\nrd 0x8+8..+4*8\r\ncrc 1K..2K\r\ncrc 1K..+1K\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        for _ in 0..5 {
+            assert_eq!(parser.parse_command(), Ok(Command::ReadData(0x10, 32)));
+        }
+        assert_eq!(parser.parse_command(), Ok(Command::Crc(1024, 1024)));
+        assert_eq!(parser.parse_command(), Ok(Command::Crc(1024, 1024)));
+    }
+
+    #[test]
+    fn parse_ranges_with_operand() {
+        let command = "fill 0x10..0x30 0xFF\r\nfill 0x10..+32 0xFF\r\nfill 0x10+2 32 0xFF\r\nverify 0..=8 0xCBF43926\r\nverify 0..+9 0xCBF43926\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::Fill(0x10, 32, 0xFF)));
+        assert_eq!(parser.parse_command(), Ok(Command::Fill(0x10, 32, 0xFF)));
+        assert_eq!(parser.parse_command(), Ok(Command::Fill(0x12, 32, 0xFF)));
+        for _ in 0..2 {
+            assert_eq!(
+                parser.parse_command(),
+                Ok(Command::Verify(0, 9, 0xCBF43926))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_sums_as_addresses() {
+        let command = "rd 0x100 + 3*32 4\r\nrd 1+2 3\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::ReadData(0x160, 4)));
+        assert_eq!(parser.parse_command(), Ok(Command::ReadData(3, 3)));
+
+        expect_error("rd 0x100 + 3*32\r\n", ParseErrorKind::UnexpectedToken, 2);
+        expect_error("fill 5+3 10\r\n", ParseErrorKind::UnexpectedToken, 3);
+    }
+
+    #[test]
+    fn parse_invalid_ranges() {
+        expect_error("rd 0x30..0x10\r\n", ParseErrorKind::OutOfRange, 2);
+        expect_error("rd 0x30..=0x2E\r\n", ParseErrorKind::OutOfRange, 2);
+        expect_error("rd 0..=0xFFFFFFFF\r\n", ParseErrorKind::OutOfRange, 2);
+        expect_error("rd 0x10..\r\n", ParseErrorKind::UnexpectedToken, 2);
+        expect_error("rd 0x10..+\r\n", ParseErrorKind::UnexpectedToken, 2);
+        expect_error("rd 0x10..+-1\r\n", ParseErrorKind::OutOfRange, 2);
+        expect_error("fill 0x10..+32\r\n", ParseErrorKind::UnexpectedToken, 3);
+        expect_error("rd 0x10+32\r\n", ParseErrorKind::UnexpectedToken, 2);
+        expect_error("fill 0x10..0x30 0x100\r\n", ParseErrorKind::OutOfRange, 3);
+        expect_error("rb 0x10..0x20\r\n", ParseErrorKind::MissingTerminator, 2);
+    }
+
+    #[test]
+    fn validate_ranges() {
+        let command = "sd x01\r\nrd 0x70..0x80\r\nrd 0x70..=0x80\r\nrd 0x70 17\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_validation(true);
+        parser.set_recovery(true);
+
+        assert!(parser.parse_command().is_ok());
+        assert_eq!(parser.parse_command(), Ok(Command::ReadData(0x70, 16)));
+        for _ in 0..2 {
+            assert_eq!(
+                parser.parse_command().unwrap_err().kind,
+                ParseErrorKind::ExceedsDevice
+            );
+        }
+    }
+
+    #[test]
+    fn parse_crc() {
+        let command = "crc 0x00000010 32\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        let res = parser.parse_command();
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Command::Crc(0x00000010, 32));
+    }
+
+    #[test]
+    fn parse_identify() {
+        let command = "id\r\nver\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::Identify));
+        assert_eq!(parser.parse_command(), Ok(Command::Version));
+    }
+
+    #[test]
+    fn parse_status() {
+        let command = "st\r\nprobe\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::Status));
+        assert_eq!(parser.parse_command(), Ok(Command::Probe));
+    }
+
+    #[test]
+    fn parse_write_data_errors() {
+        expect_error("wd 0x10\r\n", ParseErrorKind::UnexpectedToken, 2);
+        expect_error(
+            "wd 0x10 'abcdefghijklmn' 1 2 3\r\n",
+            ParseErrorKind::OutOfRange,
+            5,
+        );
+    }
+
+    #[test]
+    fn parse_with_line_endings() {
+        let command = "rb 0x10\nwb 0x20 1\r\nsd x01\r";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_line_ending(LineEnding::Any);
+
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x10)));
+        assert_eq!(parser.parse_command(), Ok(Command::WriteByte(0x20, 1)));
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::SetDevice(DeviceName::X01))
+        );
+    }
+
+    #[test]
+    fn recover_at_invalid_line_end_with_lf_line_ending() {
+        let command = "wd 0x10 'abc\nrb 0x20\nwb 0x10 -\nrb 0x30\nwd 0x10 'abc\\\nrb 0x40\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_line_ending(LineEnding::Lf);
+        parser.set_recovery(true);
+
+        for addr in [0x20, 0x30, 0x40].iter() {
+            assert_eq!(
+                parser.parse_command().unwrap_err().kind,
+                ParseErrorKind::InvalidToken
+            );
+            assert_eq!(parser.parse_command(), Ok(Command::ReadByte(*addr)));
+        }
+    }
+
+    #[test]
+    fn recover_with_lf_line_ending() {
+        let command = "rb 0x10 0x11\nrb\nrb 0x20\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+        parser.set_line_ending(LineEnding::Lf);
+        parser.set_recovery(true);
+
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::MissingTerminator
+        );
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::UnexpectedToken
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x20)));
+    }
+
+    #[test]
+    fn parse_with_tabs_and_trailing_whitespace() {
+        let command = "\twb\t0x10  \t0x42 \t\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(parser.parse_command(), Ok(Command::WriteByte(0x10, 0x42)));
+    }
+
+    #[test]
+    fn parse_script_with_comments() {
+        let command = "# main config chip\r\n\r\nsd x256  # 32 KiB\r\n  \r\nrb 0x10\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::SetDevice(DeviceName::X256))
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x10)));
+    }
+
+    #[test]
+    fn parse_ignoring_case() {
+        let command = "SD X256\r\nRb 0x10\r\nsd xM01\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::SetDevice(DeviceName::X256))
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x10)));
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::SetDevice(DeviceName::XM01))
+        );
+    }
+
+    #[test]
+    fn look_up_whole_names() {
+        assert_eq!(CommandKind::from_name(b"RB"), Some(CommandKind::ReadByte));
+        assert_eq!(CommandKind::from_name(b"rb\0zz"), None);
+        assert_eq!(CommandKind::from_name(b"rb\0"), None);
+        assert_eq!(CommandKind::from_name(b"r"), None);
+        assert_eq!(DeviceName::from_mnemonic(b"xM01"), Some(DeviceName::XM01));
+        assert_eq!(DeviceName::from_mnemonic(b"x01\0x02"), None);
+    }
+
+    #[test]
+    fn parse_long_form_aliases() {
+        let command = "set_device x01\r\nread_byte 0x10\r\nwrite_byte 0x10 1\r\nREAD 0x10 2\r\nwrite_page 3\r\nwrite_data 0x10 1\r\nchecksum 0 1\r\nidentify\r\nversion\r\nstatus\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::new(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::SetDevice(DeviceName::X01))
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::ReadByte(0x10)));
+        assert_eq!(parser.parse_command(), Ok(Command::WriteByte(0x10, 1)));
+        assert_eq!(parser.parse_command(), Ok(Command::ReadData(0x10, 2)));
+        assert_eq!(parser.parse_command(), Ok(Command::WritePage(3)));
+        assert_eq!(
+            parser.parse_command(),
+            Ok(Command::WriteData(
+                0x10,
+                ByteBuffer::try_from_slice(&[1]).unwrap()
+            ))
+        );
+        assert_eq!(parser.parse_command(), Ok(Command::Crc(0, 1)));
+        assert_eq!(parser.parse_command(), Ok(Command::Identify));
+        assert_eq!(parser.parse_command(), Ok(Command::Version));
+        assert_eq!(parser.parse_command(), Ok(Command::Status));
+    }
+
+    #[test]
+    fn command_names_fit_in_scanner() {
+        for (mnemonic, alias, _) in COMMAND_NAMES.iter() {
+            assert!(mnemonic.len() <= LONGEST_COMMAND_NAME);
+            assert!(alias.len() <= LONGEST_COMMAND_NAME);
+        }
+        assert_eq!(LONGEST_COMMAND_NAME, "write_page".len());
+    }
+
+    #[test]
+    fn parse_with_custom_capacity() {
+        let command =
+            "set_device x01\r\nwrite_data 0x10 'Hello' '!'\r\nwd 0x10 'Hello, world!'\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::<_, 10>::with_capacity(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(GenericCommand::SetDevice(DeviceName::X01))
+        );
+        assert_eq!(
+            parser.parse_command(),
+            Ok(GenericCommand::WriteData(
+                0x10,
+                ByteBuffer::try_from_slice(b"Hello!").unwrap()
+            ))
+        );
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::InvalidToken
+        );
+
+        let command = "set_device x01\r\nwd 0x10 'Hello, world!'\r\n";
+        let reader = StandardReader::new(command.as_bytes());
+        let mut parser = Parser::<_, 32>::with_capacity(reader);
+
+        assert_eq!(
+            parser.parse_command(),
+            Ok(GenericCommand::SetDevice(DeviceName::X01))
+        );
+        assert_eq!(
+            parser.parse_command(),
+            Ok(GenericCommand::WriteData(
+                0x10,
+                ByteBuffer::try_from_slice(b"Hello, world!").unwrap()
+            ))
+        );
+
+        let text = [b'A'; 200];
+        let mut command = b"wd 0x10 '".to_vec();
+        command.extend_from_slice(&text);
+        command.extend_from_slice(b"' 0x42\r\nwd 0x10 '");
+        command.extend_from_slice(&text);
+        command.extend_from_slice(&text);
+        command.extend_from_slice(b"'\r\n");
+        let reader = StandardReader::new(command.as_slice());
+        let mut parser = Parser::<_, 256>::with_capacity(reader);
+
+        let mut payload = text.to_vec();
+        payload.push(0x42);
+        assert_eq!(
+            parser.parse_command(),
+            Ok(GenericCommand::WriteData(
+                0x10,
+                ByteBuffer::try_from_slice(&payload).unwrap()
+            ))
+        );
+        assert_eq!(
+            parser.parse_command().unwrap_err().kind,
+            ParseErrorKind::InvalidToken
+        );
+    }
 }
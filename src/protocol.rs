@@ -0,0 +1,90 @@
+use crate::parser::{CommandKind, DeviceName, GenericCommand};
+
+/// The version of the command and response protocol.
+///
+/// This is incremented whenever a command or a response is added or changed, including any change
+/// to the text which is accepted or to its meaning, down to separators and line endings. That way
+/// the host can refuse to talk to a programmer built against a different protocol.
+///
+/// * 1: the commands up to `id` and `ver`.
+/// * 2: `st` and `probe`.
+/// * 3: LF-only and CR-only line endings.
+/// * 4: tabs and repeated or trailing whitespace.
+/// * 5: comments.
+/// * 6: case-insensitive names.
+/// * 7: long-form command names.
+/// * 8: 64-bit numbers.
+/// * 9: digit separators and size suffixes.
+/// * 10: integer expressions.
+/// * 11: C-style escapes in strings.
+/// * 12: character literals and hex byte strings.
+/// * 13: address ranges.
+/// * 14: the argument and the offset of the error in `ng` replies, and the error code of
+///   `ParseErrorKind::ExpressionTooDeep`.
+pub const PROTOCOL_VERSION: u16 = 14;
+
+/// Bitmaps of the commands and the devices supported by a programmer.
+///
+/// Bit n of `commands` is set when the command whose `CommandKind` discriminant is n is
+/// supported. Bit n of `devices` is set when the n-th `DeviceName` variant is supported.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Capabilities {
+    pub commands: u32,
+    pub devices: u32,
+}
+
+/// The capabilities of this build of the crate.
+pub const CAPABILITIES: Capabilities = Capabilities {
+    commands: (1 << (CommandKind::Probe as u32 + 1)) - 1,
+    devices: (1 << (DeviceName::XM02 as u32 + 1)) - 1,
+};
+
+impl Capabilities {
+    pub fn supports_command(&self, kind: CommandKind) -> bool {
+        self.commands & (1 << kind as u32) != 0
+    }
+
+    pub fn supports_device(&self, device: DeviceName) -> bool {
+        self.devices & (1 << device as u32) != 0
+    }
+
+    /// Returns true if the command and the device it selects, if any, are supported.
+    pub fn supports<const N: usize>(&self, command: &GenericCommand<N>) -> bool {
+        if let GenericCommand::SetDevice(device) = command {
+            if !self.supports_device(*device) {
+                return false;
+            }
+        }
+
+        self.supports_command(command.kind())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{Command, CommandKind, DeviceName};
+    use crate::protocol::{Capabilities, CAPABILITIES, PROTOCOL_VERSION};
+
+    #[test]
+    fn support_everything() {
+        assert!(CAPABILITIES.supports_command(CommandKind::ReadByte));
+        assert!(CAPABILITIES.supports_command(CommandKind::Probe));
+        assert!(CAPABILITIES.supports_device(DeviceName::X00));
+        assert!(CAPABILITIES.supports_device(DeviceName::XM02));
+        assert_eq!(CAPABILITIES.commands, 0x3FFF);
+        assert_eq!(CAPABILITIES.devices, 0x1FFF);
+        assert_eq!(PROTOCOL_VERSION, 14);
+    }
+
+    #[test]
+    fn check_command_support() {
+        let capabilities = Capabilities {
+            commands: 1 << CommandKind::SetDevice as u32,
+            devices: 1 << DeviceName::X256 as u32,
+        };
+
+        assert!(capabilities.supports(&Command::SetDevice(DeviceName::X256)));
+        assert!(!capabilities.supports(&Command::SetDevice(DeviceName::X512)));
+        assert!(!capabilities.supports(&Command::ReadByte(0)));
+    }
+}
@@ -0,0 +1,464 @@
+//! Replies sent from the programmer back to the host.
+//!
+//! Each response is a single line in the same token syntax as the commands:
+//!
+//! * `ok` - the command succeeded.
+//! * `ng <code> <argument> <offset>` - the command failed. See `parser::ParseErrorKind::code` for
+//!   the parse errors. The argument and the offset locate the error like in `parser::ParseError`,
+//!   so that the host can point at it.
+//! * `byte <value>` - the byte read by `rb`.
+//! * `data <value>...` - a chunk of up to `util::BYTE_BUFFER_SIZE` bytes read by `rd`. The device
+//!   sends as many chunks as needed followed by `ok`.
+//! * `crc <value>` - the CRC-32 computed for `crc`.
+//! * `id <commands> <devices>` - the capability bitmaps reported for `id`.
+//! * `ver <version>` - the protocol version reported for `ver`.
+//! * `st <device> <last error> <busy>` - the status reported for `st`. The device is `none` if no
+//!   device is selected, the last error is 0 if no error occurred and busy is 1 while a write is
+//!   in progress.
+//! * `probe <address>...` - the 7-bit addresses which acknowledged the probe, possibly none.
+//!
+//! Like command and device names, response names and `none` are matched regardless of case.
+
+use crate::encoder::SliceWriter;
+use crate::lexer::Lexer;
+use crate::parser::{DeviceName, ParseError, ParseErrorKind};
+use crate::protocol::Capabilities;
+use crate::scanner::{LineEnding, Token};
+use crate::util::{u8_str_equal_ignore_case, AddressSet, ByteBuffer};
+use core::fmt::Write;
+
+#[derive(PartialEq, Debug)]
+pub enum Response {
+    Ok,
+    Error(ErrorReport),
+    Byte(u8),
+    Data(ByteBuffer),
+    Crc(u32),
+    Identity(Capabilities),
+    Version(u16),
+    Status(Status),
+    Probe(AddressSet),
+}
+
+/// The error reported in an `ng` reply.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ErrorReport {
+    pub code: u8,
+    /// The index of the argument which failed, where 0 is the command name itself.
+    pub argument: u32,
+    /// The zero-based byte offset of the failing token within its line.
+    pub offset: u32,
+}
+
+impl From<&ParseError> for ErrorReport {
+    fn from(error: &ParseError) -> ErrorReport {
+        ErrorReport {
+            code: error.kind.code(),
+            argument: error.argument as u32,
+            offset: error.offset as u32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Status {
+    pub device: Option<DeviceName>,
+    /// The code of the last error, or 0 if no error has occurred.
+    pub last_error: u8,
+    pub write_in_progress: bool,
+}
+
+pub struct ResponseParser<R> {
+    lexer: Lexer<R>,
+}
+
+impl<R> ResponseParser<R>
+where
+    R: crate::reader::Reader,
+{
+    pub fn new(reader: R) -> ResponseParser<R> {
+        ResponseParser {
+            lexer: Lexer::new(reader),
+        }
+    }
+
+    /// Resynchronizes to the next line like `Lexer::recover`.
+    pub fn recover(&mut self) -> Result<(), ParseError> {
+        self.lexer.recover()
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.lexer.set_line_ending(line_ending);
+    }
+
+    pub fn destroy(self: ResponseParser<R>) -> R {
+        self.lexer.destroy()
+    }
+
+    pub fn parse_response(&mut self) -> Result<Response, ParseError> {
+        self.lexer.start();
+        let res_str = match self.lexer.get_token()? {
+            Token::Identifier(name) => name,
+            _ => return Err(self.lexer.error(ParseErrorKind::UnexpectedToken)),
+        };
+
+        if u8_str_equal_ignore_case(res_str, b"ok") {
+            self.lexer.expect_finish()?;
+            Ok(Response::Ok)
+        } else if u8_str_equal_ignore_case(res_str, b"ng") {
+            let code = self.parse_byte()?;
+            let argument = self.parse_u32()?;
+            let offset = self.parse_u32()?;
+            self.lexer.expect_finish()?;
+            Ok(Response::Error(ErrorReport {
+                code,
+                argument,
+                offset,
+            }))
+        } else if u8_str_equal_ignore_case(res_str, b"byte") {
+            let data = self.parse_byte()?;
+            self.lexer.expect_finish()?;
+            Ok(Response::Byte(data))
+        } else if u8_str_equal_ignore_case(res_str, b"data") {
+            self.parse_data()
+        } else if u8_str_equal_ignore_case(res_str, b"crc") {
+            let crc = self.parse_u32()?;
+            self.lexer.expect_finish()?;
+            Ok(Response::Crc(crc))
+        } else if u8_str_equal_ignore_case(res_str, b"id") {
+            let commands = self.parse_u32()?;
+            let devices = self.parse_u32()?;
+            self.lexer.expect_finish()?;
+            Ok(Response::Identity(Capabilities { commands, devices }))
+        } else if u8_str_equal_ignore_case(res_str, b"ver") {
+            let version = self.lexer.expect_number()?;
+            if !(0..=0xFFFF).contains(&version) {
+                return Err(self.lexer.error(ParseErrorKind::OutOfRange));
+            }
+            self.lexer.expect_finish()?;
+            Ok(Response::Version(version as u16))
+        } else if u8_str_equal_ignore_case(res_str, b"st") {
+            self.parse_status()
+        } else if u8_str_equal_ignore_case(res_str, b"probe") {
+            let addresses = self.parse_address_list()?;
+            Ok(Response::Probe(addresses))
+        } else {
+            Err(self.lexer.error(ParseErrorKind::UnknownResponse))
+        }
+    }
+
+    fn parse_byte(&mut self) -> Result<u8, ParseError> {
+        let value = self.lexer.expect_number()?;
+        if !(0..=255).contains(&value) {
+            Err(self.lexer.error(ParseErrorKind::OutOfRange))
+        } else {
+            Ok(value as u8)
+        }
+    }
+
+    fn parse_u32(&mut self) -> Result<u32, ParseError> {
+        let bitmap = self.lexer.expect_number()?;
+        if !(0..=u32::MAX as i128).contains(&bitmap) {
+            Err(self.lexer.error(ParseErrorKind::OutOfRange))
+        } else {
+            Ok(bitmap as u32)
+        }
+    }
+
+    fn parse_data(&mut self) -> Result<Response, ParseError> {
+        let data = self.parse_byte_list()?;
+        if data.is_empty() {
+            Err(self.lexer.error(ParseErrorKind::UnexpectedToken))
+        } else {
+            Ok(Response::Data(data))
+        }
+    }
+
+    fn parse_byte_list(&mut self) -> Result<ByteBuffer, ParseError> {
+        let mut data = ByteBuffer::default();
+        loop {
+            match self.lexer.get_token()? {
+                Token::Number(value) => {
+                    if !(0..=255).contains(&value) || data.push(value as u8).is_err() {
+                        return Err(self.lexer.error(ParseErrorKind::OutOfRange));
+                    }
+                }
+                Token::Finish => return Ok(data),
+                _ => return Err(self.lexer.error(ParseErrorKind::UnexpectedToken)),
+            }
+        }
+    }
+
+    fn parse_address_list(&mut self) -> Result<AddressSet, ParseError> {
+        let mut addresses = AddressSet::default();
+        loop {
+            match self.lexer.get_token()? {
+                Token::Number(value) => {
+                    if !(0..=255).contains(&value) || addresses.insert(value as u8).is_err() {
+                        return Err(self.lexer.error(ParseErrorKind::OutOfRange));
+                    }
+                }
+                Token::Finish => return Ok(addresses),
+                _ => return Err(self.lexer.error(ParseErrorKind::UnexpectedToken)),
+            }
+        }
+    }
+
+    fn parse_status(&mut self) -> Result<Response, ParseError> {
+        let device_name_str = match self.lexer.get_token()? {
+            Token::Identifier(name) => name,
+            _ => return Err(self.lexer.error(ParseErrorKind::UnexpectedToken)),
+        };
+        let device = if u8_str_equal_ignore_case(device_name_str, b"none") {
+            None
+        } else {
+            match DeviceName::from_mnemonic(device_name_str) {
+                Some(device_name) => Some(device_name),
+                None => return Err(self.lexer.error(ParseErrorKind::UnknownDevice)),
+            }
+        };
+        let last_error = self.parse_byte()?;
+        let write_in_progress = match self.lexer.expect_number()? {
+            0 => false,
+            1 => true,
+            _ => return Err(self.lexer.error(ParseErrorKind::OutOfRange)),
+        };
+        self.lexer.expect_finish()?;
+        Ok(Response::Status(Status {
+            device,
+            last_error,
+            write_in_progress,
+        }))
+    }
+}
+
+/// Writes the response in the text format accepted by `ResponseParser`, including the line
+/// terminator.
+pub fn encode<W>(response: &Response, w: &mut W) -> core::fmt::Result
+where
+    W: Write,
+{
+    match response {
+        Response::Ok => write!(w, "ok\r\n"),
+        Response::Error(error) => write!(
+            w,
+            "ng {} {} {}\r\n",
+            error.code, error.argument, error.offset
+        ),
+        Response::Byte(data) => write!(w, "byte 0x{:02X}\r\n", data),
+        Response::Data(data) => {
+            write!(w, "data")?;
+            for b in data.as_slice() {
+                write!(w, " 0x{:02X}", b)?;
+            }
+            write!(w, "\r\n")
+        }
+        Response::Crc(crc) => write!(w, "crc 0x{:08X}\r\n", crc),
+        Response::Identity(capabilities) => write!(
+            w,
+            "id 0x{:08X} 0x{:08X}\r\n",
+            capabilities.commands, capabilities.devices
+        ),
+        Response::Version(version) => write!(w, "ver {}\r\n", version),
+        Response::Status(status) => write!(
+            w,
+            "st {} {} {}\r\n",
+            match &status.device {
+                Some(device_name) => device_name.mnemonic(),
+                None => "none",
+            },
+            status.last_error,
+            status.write_in_progress as u8
+        ),
+        Response::Probe(addresses) => {
+            write!(w, "probe")?;
+            for address in addresses.iter() {
+                write!(w, " 0x{:02X}", address)?;
+            }
+            write!(w, "\r\n")
+        }
+    }
+}
+
+/// Writes the response into the buffer and returns the number of bytes written.
+///
+/// Fails if the buffer is too small to hold the whole response.
+pub fn encode_to_slice(response: &Response, buffer: &mut [u8]) -> Result<usize, core::fmt::Error> {
+    let mut w = SliceWriter::new(buffer);
+    encode(response, &mut w)?;
+    Ok(w.len())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{DeviceName, ParseError, ParseErrorKind};
+    use crate::protocol::{CAPABILITIES, PROTOCOL_VERSION};
+    use crate::reader::StandardReader;
+    use crate::response::{encode, encode_to_slice, ErrorReport, Response, ResponseParser, Status};
+    use crate::util::{AddressSet, ByteBuffer};
+
+    fn expect_round_trip(response: Response) {
+        let mut buffer = [0; 1024];
+        let len = encode_to_slice(&response, &mut buffer).unwrap();
+        let reader = StandardReader::new(&buffer[..len]);
+        let mut parser = ResponseParser::new(reader);
+
+        assert_eq!(parser.parse_response(), Ok(response));
+    }
+
+    #[test]
+    fn encode_data() {
+        let mut s = String::new();
+        let data = ByteBuffer::try_from_slice(&[0x01, 0xAB]).unwrap();
+        encode(&Response::Data(data), &mut s).unwrap();
+        assert_eq!(s, "data 0x01 0xAB\r\n");
+    }
+
+    #[test]
+    fn encode_error() {
+        let error = ParseError {
+            kind: ParseErrorKind::UnexpectedToken,
+            argument: 2,
+            line: 3,
+            offset: 9,
+        };
+        let mut s = String::new();
+        encode(&Response::Error(ErrorReport::from(&error)), &mut s).unwrap();
+        assert_eq!(s, "ng 4 2 9\r\n");
+
+        let reader = StandardReader::new(s.as_bytes());
+        let mut parser = ResponseParser::new(reader);
+        assert_eq!(
+            parser.parse_response(),
+            Ok(Response::Error(ErrorReport {
+                code: ParseErrorKind::UnexpectedToken.code(),
+                argument: 2,
+                offset: 9,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_read_data_stream() {
+        let response = "data 0x01 0x02\r\ndata 0x03\r\nok\r\n";
+        let reader = StandardReader::new(response.as_bytes());
+        let mut parser = ResponseParser::new(reader);
+
+        assert_eq!(
+            parser.parse_response(),
+            Ok(Response::Data(
+                ByteBuffer::try_from_slice(&[0x01, 0x02]).unwrap()
+            ))
+        );
+        assert_eq!(
+            parser.parse_response(),
+            Ok(Response::Data(ByteBuffer::try_from_slice(&[0x03]).unwrap()))
+        );
+        assert_eq!(parser.parse_response(), Ok(Response::Ok));
+    }
+
+    #[test]
+    fn parse_probe() {
+        let response = "probe 0x51 0x50\r\nprobe 0x80\r\n";
+        let reader = StandardReader::new(response.as_bytes());
+        let mut parser = ResponseParser::new(reader);
+
+        assert_eq!(
+            parser.parse_response(),
+            Ok(Response::Probe(
+                AddressSet::try_from_slice(&[0x50, 0x51]).unwrap()
+            ))
+        );
+        assert_eq!(
+            parser.parse_response().unwrap_err().kind,
+            ParseErrorKind::OutOfRange
+        );
+    }
+
+    #[test]
+    fn round_trip_full_probe() {
+        let mut addresses = AddressSet::default();
+        for address in 0x08..=0x77 {
+            addresses.insert(address).unwrap();
+        }
+
+        assert_eq!(addresses.len(), 112);
+        expect_round_trip(Response::Probe(addresses));
+    }
+
+    #[test]
+    fn parse_status() {
+        let response = "st x01 0 1\r\n";
+        let reader = StandardReader::new(response.as_bytes());
+        let mut parser = ResponseParser::new(reader);
+
+        assert_eq!(
+            parser.parse_response(),
+            Ok(Response::Status(Status {
+                device: Some(DeviceName::X01),
+                last_error: 0,
+                write_in_progress: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_response_ignoring_case() {
+        let response = "OK\r\nSt NONE 0 0\r\n";
+        let reader = StandardReader::new(response.as_bytes());
+        let mut parser = ResponseParser::new(reader);
+
+        assert_eq!(parser.parse_response(), Ok(Response::Ok));
+        assert_eq!(
+            parser.parse_response(),
+            Ok(Response::Status(Status {
+                device: None,
+                last_error: 0,
+                write_in_progress: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_unknown_response() {
+        let response = "maybe\r\n";
+        let reader = StandardReader::new(response.as_bytes());
+        let mut parser = ResponseParser::new(reader);
+
+        assert_eq!(
+            parser.parse_response().unwrap_err().kind,
+            ParseErrorKind::UnknownResponse
+        );
+    }
+
+    #[test]
+    fn round_trip_responses() {
+        expect_round_trip(Response::Ok);
+        expect_round_trip(Response::Error(ErrorReport {
+            code: ParseErrorKind::OutOfRange.code(),
+            argument: 2,
+            offset: 9,
+        }));
+        expect_round_trip(Response::Byte(0x42));
+        expect_round_trip(Response::Data(
+            ByteBuffer::try_from_slice(&[0xFF; 16]).unwrap(),
+        ));
+        expect_round_trip(Response::Crc(0xCBF43926));
+        expect_round_trip(Response::Identity(CAPABILITIES));
+        expect_round_trip(Response::Version(PROTOCOL_VERSION));
+        expect_round_trip(Response::Status(Status {
+            device: Some(DeviceName::X256),
+            last_error: 0,
+            write_in_progress: true,
+        }));
+        expect_round_trip(Response::Status(Status {
+            device: None,
+            last_error: ParseErrorKind::UnknownCommand.code(),
+            write_in_progress: false,
+        }));
+        expect_round_trip(Response::Probe(
+            AddressSet::try_from_slice(&[0x50, 0x51]).unwrap(),
+        ));
+        expect_round_trip(Response::Probe(AddressSet::default()));
+    }
+}
@@ -1,13 +1,39 @@
 #[cfg(feature = "display")]
 use core::fmt::Formatter;
 
-const SCANNED_STRING_BUFFER_SIZE: usize = 16;
-
-pub struct Scanner {
+/// The default capacity of the buffer holding scanned identifiers and strings.
+pub const SCANNED_STRING_BUFFER_SIZE: usize = 16;
+
+/// The scanner for the command text.
+///
+/// `N` is the capacity of the buffer holding identifiers and strings. Longer ones are scanned as
+/// `Token::Invalid`.
+pub struct Scanner<const N: usize = SCANNED_STRING_BUFFER_SIZE> {
     state: ScannerState,
-    pub(crate) scanned_string: [u8; SCANNED_STRING_BUFFER_SIZE],
-    pub(crate) scanned_number: i32,
+    line_ending: LineEnding,
+    pending: Option<TokenKind>,
+    /// A character which arrived while a token was still pending, to be scanned after it.
+    deferred: Option<u8>,
+    skip_line_feed: bool,
+    line_has_tokens: bool,
+    string_buffer: [u8; N],
+    string_len: usize,
+    escaped_byte: u8,
+    /// The magnitude of the scanned number, whose sign is kept in `scanned_number_sign`.
+    scanned_magnitude: u64,
     scanned_number_sign: Sign,
+    /// Set when the last `Token::Invalid` was caused by a number whose magnitude does not fit in a
+    /// `u64`.
+    number_overflow: bool,
+    /// Set once the scanned number has a digit after its prefix.
+    number_has_digits: bool,
+    scanned_operator: Operator,
+    offset: usize,
+    token_offset: usize,
+    /// The offset of a token started by the character completing the previous one.
+    next_token_offset: Option<usize>,
+    token_line: usize,
+    line: usize,
 }
 
 pub enum ScannerState {
@@ -18,40 +44,261 @@ pub enum ScannerState {
     NumberWithSign,
     AnyNumber,
     Escape,
+    HexEscapeHigh,
+    HexEscapeLow,
+    HexString,
+    HexStringLow,
+    HexStringEnd,
     StringEnd,
     DecimalNumber,
     BinaryNumber,
     OctalNumber,
     HexadecimalNumber,
+    Suffix,
+    /// After the letter of a multiplier suffix, which may be followed by `i`.
+    MultiplierSuffix,
+    ShiftLeft,
+    ShiftRight,
+    AfterRightParen,
+    Dot,
+    DotDot,
+    Comment,
+}
+
+/// The line terminators accepted by the scanner.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineEnding {
+    /// Only CR LF, which is the default.
+    #[default]
+    CrLf,
+    /// Only LF.
+    Lf,
+    /// Only CR.
+    Cr,
+    /// Any of CR LF, LF and CR.
+    Any,
+}
+
+/// A token scanned from the command text, with its value.
+///
+/// Identifiers and strings borrow the buffer of the scanner, so they are only valid until the
+/// next character is scanned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token<'a> {
+    Identifier(&'a [u8]),
+    String(&'a [u8]),
+    /// A string of bytes written as pairs of hexadecimal digits, like `h"DEADBEEF"`.
+    HexString(&'a [u8]),
+    /// An integer, which is also how a quoted single character like `'a'` is scanned.
+    ///
+    /// Numbers are scanned as a sign and a 64-bit magnitude, so the value is in the range
+    /// `-0xFFFF_FFFF_FFFF_FFFF..=0xFFFF_FFFF_FFFF_FFFF`. Digits may be separated by `_`, and a
+    /// number may end with a binary multiplier suffix: `K` or `Ki` for 1024 and `M` or `Mi` for
+    /// 1048576, in either case.
+    Number(i128),
+    Operator(Operator),
+    LeftParen,
+    RightParen,
+    /// The `..` of an address range, which excludes the end.
+    Range,
+    /// The `..=` of an address range, which includes the end.
+    RangeInclusive,
+    /// The `..+` of an address range given by its start and its length.
+    RangeLength,
+    Finish,
+    Invalid,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Token {
+/// The kind of a token, whose value is kept by the scanner until the next token is scanned.
+///
+/// Unlike `Token`, it does not borrow the scanner, so the position of the token can be queried
+/// before its value is looked up with `Scanner::token`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
     Identifier,
     String,
+    HexString,
     Number,
+    Operator,
+    LeftParen,
+    RightParen,
+    Range,
+    RangeInclusive,
+    RangeLength,
     Finish,
     Invalid,
 }
 
+/// The binary operators allowed in numeric expressions.
+///
+/// A `-` is scanned as `Operator::Subtract` when it directly follows a number or a closing
+/// parenthesis, or when it is followed by a separator or an opening parenthesis. Otherwise it is
+/// the sign of a number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    ShiftLeft,
+    ShiftRight,
+    Or,
+    And,
+}
+
 #[derive(PartialEq)]
 enum Sign {
     Positive,
     Negative,
 }
 
-impl Scanner {
-    pub fn default() -> Scanner {
+/// Returns true for the letters of the multiplier suffixes of numbers, which are `k`, `K`, `m` and
+/// `M`.
+fn is_suffix(c: u8) -> bool {
+    c == b'k' || c == b'K' || c == b'm' || c == b'M'
+}
+
+/// Returns true for the characters which end a number and start an operator, including the
+/// closing parenthesis.
+fn is_operator(c: u8) -> bool {
+    b"+-*/<>|&)".contains(&c)
+}
+
+/// Returns true for the characters separating tokens, which are spaces and tabs.
+fn is_separator(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
+impl<const N: usize> Default for Scanner<N> {
+    fn default() -> Scanner<N> {
         Scanner {
             state: ScannerState::Initial,
-            scanned_string: [0; SCANNED_STRING_BUFFER_SIZE],
-            scanned_number: 0,
+            line_ending: LineEnding::default(),
+            pending: None,
+            deferred: None,
+            skip_line_feed: false,
+            line_has_tokens: false,
+            string_buffer: [0; N],
+            string_len: 0,
+            escaped_byte: 0,
+            scanned_magnitude: 0,
             scanned_number_sign: Sign::Positive,
+            number_overflow: false,
+            number_has_digits: false,
+            scanned_operator: Operator::Add,
+            offset: 0,
+            token_offset: 0,
+            next_token_offset: None,
+            token_line: 0,
+            line: 0,
+        }
+    }
+}
+
+impl Scanner {
+    /// Creates a scanner whose buffer holds `SCANNED_STRING_BUFFER_SIZE` bytes.
+    ///
+    /// Use `Scanner::<N>::default()` for another capacity.
+    pub fn new() -> Scanner {
+        Scanner::default()
+    }
+}
+
+impl<const N: usize> Scanner<N> {
+    pub fn set_line_ending(self: &mut Scanner<N>, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// Scans one character and returns the token it completes, if any.
+    ///
+    /// A line terminator may complete two tokens at once when it is not preceded by a separator.
+    /// In that case the second one is returned by `take_pending`, which should be called after
+    /// each token. If it is not, the next call returns the second token instead and `c` is scanned
+    /// after it, so that no token is lost.
+    ///
+    /// The token borrows the scanner mutably. Use `scan` and `token` instead to query the scanner
+    /// while holding the token.
+    pub fn scan_command(self: &mut Scanner<N>, c: u8) -> Option<Token<'_>> {
+        let kind = self.scan(c)?;
+        Some(self.token(kind))
+    }
+
+    /// Returns the token completed by the last character in addition to the one returned from
+    /// `scan_command`, if any.
+    ///
+    /// Also scans the character kept back by `scan_command` when it returned a pending token.
+    pub fn take_pending(self: &mut Scanner<N>) -> Option<Token<'_>> {
+        let kind = self.take_pending_kind()?;
+        Some(self.token(kind))
+    }
+
+    /// Returns true if the last `Token::Invalid` was caused by a number whose magnitude does not
+    /// fit in a `u64`.
+    pub fn number_overflowed(self: &Scanner<N>) -> bool {
+        self.number_overflow
+    }
+
+    /// Returns the token of the given kind with the value scanned last.
+    pub fn token(self: &Scanner<N>, kind: TokenKind) -> Token<'_> {
+        match kind {
+            TokenKind::Identifier => Token::Identifier(self.scanned_string()),
+            TokenKind::String => Token::String(self.scanned_string()),
+            TokenKind::HexString => Token::HexString(self.scanned_string()),
+            TokenKind::Number => Token::Number(self.scanned_number()),
+            TokenKind::Operator => Token::Operator(self.scanned_operator),
+            TokenKind::LeftParen => Token::LeftParen,
+            TokenKind::RightParen => Token::RightParen,
+            TokenKind::Range => Token::Range,
+            TokenKind::RangeInclusive => Token::RangeInclusive,
+            TokenKind::RangeLength => Token::RangeLength,
+            TokenKind::Finish => Token::Finish,
+            TokenKind::Invalid => Token::Invalid,
         }
     }
 
-    pub fn scan_command(self: &mut Scanner, c: u8) -> Option<Token> {
+    /// Scans one character like `scan_command` but returns only the kind of the token.
+    pub fn scan(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if let Some(token) = self.take_pending_kind() {
+            // A pending token is only ever followed by a state in which no character completes
+            // two tokens, so nothing is pending while a character is deferred
+            self.deferred = Some(c);
+            return Some(token);
+        }
+
+        self.scan_char(c)
+    }
+
+    fn scan_char(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if self.skip_line_feed {
+            self.skip_line_feed = false;
+            // The line was already finished by the preceding carriage return
+            if c == b'\n' {
+                return None;
+            }
+        }
+
+        if let Some(offset) = self.next_token_offset.take() {
+            self.token_offset = offset;
+        }
+        // A token never spans lines, so the line of any character is also the line of its token
+        self.token_line = self.line;
+
         match self.state {
+            ScannerState::Initial | ScannerState::AfterRightParen => {
+                self.token_offset = self.offset;
+                self.number_overflow = false;
+            }
+            // The operator starts at the preceding character
+            ScannerState::ShiftLeft | ScannerState::ShiftRight | ScannerState::Dot => {
+                self.token_offset = self.offset - 1
+            }
+            ScannerState::DotDot => self.token_offset = self.offset - 2,
+            // The terminator starts at the preceding carriage return
+            ScannerState::Finish => self.token_offset = self.offset - 1,
+            _ => (),
+        }
+
+        let res = match self.state {
             ScannerState::Initial => self.scan_when_initial(c),
             ScannerState::Identifier => self.scan_when_identifier(c),
             ScannerState::Finish => self.scan_when_finish(c),
@@ -59,67 +306,288 @@ impl Scanner {
             ScannerState::NumberWithSign => self.scan_when_number_with_sign(c),
             ScannerState::AnyNumber => self.scan_when_any_number(c),
             ScannerState::Escape => self.scan_when_escape(c),
+            ScannerState::HexEscapeHigh | ScannerState::HexEscapeLow => {
+                self.scan_when_hex_escape(c)
+            }
+            ScannerState::HexString | ScannerState::HexStringLow => self.scan_when_hex_string(c),
+            ScannerState::HexStringEnd => self.scan_when_hex_string_end(c),
             ScannerState::StringEnd => self.scan_when_string_end(c),
             ScannerState::DecimalNumber => self.scan_when_decimal_number(c),
             ScannerState::BinaryNumber => self.scan_when_binary_number(c),
             ScannerState::OctalNumber => self.scan_when_octal_number(c),
             ScannerState::HexadecimalNumber => self.scan_when_hexadecimal_number(c),
+            ScannerState::Suffix => self.scan_when_suffix(c),
+            ScannerState::MultiplierSuffix => self.scan_when_multiplier_suffix(c),
+            ScannerState::ShiftLeft => self.scan_when_shift(c, b'<', Operator::ShiftLeft),
+            ScannerState::ShiftRight => self.scan_when_shift(c, b'>', Operator::ShiftRight),
+            ScannerState::AfterRightParen => self.scan_when_after_right_paren(c),
+            ScannerState::Dot => self.scan_when_dot(c),
+            ScannerState::DotDot => self.scan_when_dot_dot(c),
+            ScannerState::Comment => self.scan_when_comment(c),
+        };
+
+        let res = if res == Some(TokenKind::Invalid) && self.is_line_end(c) {
+            // The terminator which made the token invalid still ends the line
+            self.end_line(c, res)
+        } else {
+            res
+        };
+
+        let line_finished = res == Some(TokenKind::Finish);
+        let res = match res {
+            // Blank lines and lines with only a comment are skipped silently
+            Some(TokenKind::Finish) if !self.line_has_tokens => None,
+            Some(token) => {
+                self.line_has_tokens = true;
+                Some(token)
+            }
+            None => None,
+        };
+
+        self.advance(line_finished);
+        res
+    }
+
+    /// Returns the kind of the pending token like `take_pending`.
+    pub fn take_pending_kind(self: &mut Scanner<N>) -> Option<TokenKind> {
+        let res = self.pending.take();
+        if res.is_some() {
+            // The pending token is the last character scanned
+            self.token_offset = self.offset - 1;
+            self.token_line = self.line;
+            if res == Some(TokenKind::Finish) {
+                self.advance(true);
+            }
+        } else if let Some(c) = self.deferred.take() {
+            return self.scan_char(c);
         }
+
+        res
+    }
+
+    /// Returns the last identifier or string scanned.
+    fn scanned_string(self: &Scanner<N>) -> &[u8] {
+        &self.string_buffer[..self.string_len]
     }
 
-    fn clear_scanned_number(self: &mut Scanner) {
-        self.scanned_number = 0;
+    /// Returns the zero-based byte offset of the last token within its line.
+    pub fn token_offset(self: &Scanner<N>) -> usize {
+        self.token_offset
+    }
+
+    /// Returns the zero-based number of the line of the last token, counted since the scanner was
+    /// created.
+    ///
+    /// Unlike `line`, this is still the line of the terminator after a `Token::Finish`.
+    pub fn token_line(self: &Scanner<N>) -> usize {
+        self.token_line
+    }
+
+    /// Returns the zero-based number of the line being scanned, counted since the scanner was
+    /// created.
+    pub fn line(self: &Scanner<N>) -> usize {
+        self.line
+    }
+
+    fn advance(self: &mut Scanner<N>, line_finished: bool) {
+        if line_finished {
+            self.line += 1;
+            self.offset = 0;
+            self.line_has_tokens = false;
+        } else {
+            self.offset += 1;
+        }
+    }
+
+    /// Discards any partially scanned token and returns to the initial state.
+    pub fn reset(self: &mut Scanner<N>) {
+        self.state = ScannerState::Initial;
+    }
+
+    /// Consumes one character while discarding the rest of the current line.
+    ///
+    /// Returns true once the line terminator has been consumed, leaving the scanner in the initial
+    /// state.
+    pub fn skip_line(self: &mut Scanner<N>, c: u8) -> bool {
+        if self.skip_line_feed {
+            self.skip_line_feed = false;
+            if c == b'\n' {
+                return false;
+            }
+        }
+
+        if let ScannerState::Finish = self.state {
+            if c == b'\n' {
+                self.state = ScannerState::Initial;
+                self.advance(true);
+                return true;
+            }
+        }
+
+        if self.line_ending != LineEnding::CrLf && self.is_line_end(c) {
+            self.state = ScannerState::Initial;
+            self.skip_line_feed = self.line_ending == LineEnding::Any && c == b'\r';
+            self.advance(true);
+            return true;
+        }
+
+        if c == b'\r' {
+            self.state = ScannerState::Finish;
+        } else {
+            self.state = ScannerState::Initial;
+        }
+        self.advance(false);
+        false
+    }
+
+    fn is_line_end(self: &Scanner<N>, c: u8) -> bool {
+        match self.line_ending {
+            LineEnding::CrLf | LineEnding::Cr => c == b'\r',
+            LineEnding::Lf => c == b'\n',
+            LineEnding::Any => c == b'\r' || c == b'\n',
+        }
+    }
+
+    /// Finishes the line at the terminator `c`, after the token it completes if any.
+    fn end_line(self: &mut Scanner<N>, c: u8, token: Option<TokenKind>) -> Option<TokenKind> {
+        if self.line_ending == LineEnding::CrLf {
+            // The line is finished by the following line feed
+            self.state = ScannerState::Finish;
+            return token;
+        }
+
+        self.state = ScannerState::Initial;
+        self.skip_line_feed = self.line_ending == LineEnding::Any && c == b'\r';
+        self.queue(token, TokenKind::Finish)
+    }
+
+    /// Returns `token` and keeps `next` for `take_pending`, or returns `next` if there is no
+    /// token.
+    fn queue(
+        self: &mut Scanner<N>,
+        token: Option<TokenKind>,
+        next: TokenKind,
+    ) -> Option<TokenKind> {
+        if token.is_some() {
+            self.pending = Some(next);
+            token
+        } else {
+            Some(next)
+        }
+    }
+
+    /// Scans the operator or the closing parenthesis `c`, after the number it completes if any.
+    fn scan_operator(self: &mut Scanner<N>, c: u8, token: Option<TokenKind>) -> Option<TokenKind> {
+        let operator = match c {
+            b'+' => Operator::Add,
+            b'-' => Operator::Subtract,
+            b'*' => Operator::Multiply,
+            b'/' => Operator::Divide,
+            b'|' => Operator::Or,
+            b'&' => Operator::And,
+            b'<' => {
+                self.state = ScannerState::ShiftLeft;
+                return token;
+            }
+            b'>' => {
+                self.state = ScannerState::ShiftRight;
+                return token;
+            }
+            _ => {
+                self.state = ScannerState::AfterRightParen;
+                return self.queue(token, TokenKind::RightParen);
+            }
+        };
+
+        self.state = ScannerState::Initial;
+        self.scanned_operator = operator;
+        self.queue(token, TokenKind::Operator)
+    }
+
+    fn scanned_number(self: &Scanner<N>) -> i128 {
+        let magnitude = self.scanned_magnitude as i128;
+        if self.scanned_number_sign == Sign::Negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    fn clear_scanned_number(self: &mut Scanner<N>) {
+        self.scanned_magnitude = 0;
         self.scanned_number_sign = Sign::Positive;
+        self.number_has_digits = false;
     }
 
-    fn clear_scanned_string(self: &mut Scanner) {
-        self.scanned_string = [0; SCANNED_STRING_BUFFER_SIZE];
+    fn clear_scanned_string(self: &mut Scanner<N>) {
+        self.string_len = 0;
     }
 
-    fn push_digit(self: &mut Scanner, d: u8, radix: u8) -> Result<(), ()> {
+    fn push_digit(self: &mut Scanner<N>, d: u8, radix: u8) -> Result<(), ()> {
         match self
-            .scanned_number
-            .checked_mul(radix as i32)
-            .and_then(|r| r.checked_add(d as i32))
+            .scanned_magnitude
+            .checked_mul(radix as u64)
+            .and_then(|r| r.checked_add(d as u64))
         {
             Some(new_value) => {
-                self.scanned_number = new_value;
+                self.scanned_magnitude = new_value;
+                self.number_has_digits = true;
                 Ok(())
             }
-            None => Err(()),
+            None => {
+                self.number_overflow = true;
+                Err(())
+            }
         }
     }
 
-    fn finalize_scanned_number(self: &mut Scanner) -> Result<(), ()> {
-        if self.scanned_number_sign == Sign::Negative {
-            match self.scanned_number.checked_mul(-1) {
-                Some(new_value) => {
-                    self.scanned_number = new_value;
-                    Ok(())
-                }
-                None => Err(()),
-            }
+    /// Multiplies the scanned number by the suffix starting with `c`.
+    ///
+    /// The multipliers are binary whatever the case of the letter and whether `i` follows it, so
+    /// `k`, `K`, `ki` and `Ki` are 1024 and `m`, `M`, `mi` and `Mi` are 1048576.
+    fn scan_suffix(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        let multiplier = if c == b'k' || c == b'K' {
+            1024
         } else {
-            Ok(())
+            1024 * 1024
+        };
+        match self.scanned_magnitude.checked_mul(multiplier) {
+            Some(new_value) => {
+                self.scanned_magnitude = new_value;
+                self.state = ScannerState::MultiplierSuffix;
+                None
+            }
+            None => {
+                self.number_overflow = true;
+                self.state = ScannerState::Initial;
+                Some(TokenKind::Invalid)
+            }
         }
     }
 
-    fn push_char(self: &mut Scanner, c: u8) -> Result<(), ()> {
-        for i in 0..SCANNED_STRING_BUFFER_SIZE - 1 {
-            if self.scanned_string[i] == b'\0' {
-                self.scanned_string[i] = c;
-                return Ok(());
-            }
+    /// Returns true if the number has no digit after its prefix, like `0x_`, and does not continue
+    /// with `c`, so that it ends without one.
+    fn lacks_digits(self: &Scanner<N>, c: u8, radix: u32) -> bool {
+        !self.number_has_digits && c != b'_' && (c as char).to_digit(radix).is_none()
+    }
+
+    fn push_char(self: &mut Scanner<N>, c: u8) -> Result<(), ()> {
+        if self.string_len >= N {
+            return Err(());
         }
 
-        Err(())
+        self.string_buffer[self.string_len] = c;
+        self.string_len += 1;
+        Ok(())
     }
 
-    fn scan_when_initial(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
+    fn scan_when_initial(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if is_separator(c) {
             None
-        } else if c == b'\r' {
-            self.state = ScannerState::Finish;
+        } else if self.is_line_end(c) {
+            self.end_line(c, None)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
             None
         } else if c == b'-' {
             self.clear_scanned_number();
@@ -127,16 +595,24 @@ impl Scanner {
 
             self.state = ScannerState::NumberWithSign;
             None
+        } else if c == b'(' {
+            Some(TokenKind::LeftParen)
+        } else if c == b'.' {
+            self.state = ScannerState::Dot;
+            None
+        } else if is_operator(c) {
+            self.scan_operator(c, None)
         } else if c == b'0' {
             self.clear_scanned_number();
+            self.number_has_digits = true;
 
             self.state = ScannerState::AnyNumber;
             None
-        } else if b'1' <= c && c <= b'9' {
+        } else if (b'1'..=b'9').contains(&c) {
             self.clear_scanned_number();
             if self.push_digit(c - b'0', 10).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             self.state = ScannerState::DecimalNumber;
@@ -146,322 +622,557 @@ impl Scanner {
 
             self.state = ScannerState::String;
             None
-        } else if (b'a' <= c && c <= b'z') || (b'A' <= c && c <= b'Z') {
+        } else if c.is_ascii_alphabetic() {
             self.clear_scanned_string();
             if self.push_char(c).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             self.state = ScannerState::Identifier;
             None
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_identifier(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
+    fn scan_when_identifier(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if is_separator(c) {
             self.state = ScannerState::Initial;
-            Some(Token::Identifier)
-        } else if c == b'\r' {
-            self.state = ScannerState::Finish;
-            Some(Token::Identifier)
-        } else if c == b'_'
-            || (b'a' <= c && c <= b'z')
-            || (b'A' <= c && c <= b'Z')
-            || (b'0' <= c && c <= b'9')
-        {
+            Some(TokenKind::Identifier)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::Identifier)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::Identifier))
+        } else if c == b'"' && self.scanned_string().eq_ignore_ascii_case(b"h") {
+            self.clear_scanned_string();
+
+            self.state = ScannerState::HexString;
+            None
+        } else if c == b'_' || c.is_ascii_alphanumeric() {
             if self.push_char(c).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             None
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_finish(self: &mut Scanner, c: u8) -> Option<Token> {
+    fn scan_when_finish(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
         if c == b'\n' {
             self.state = ScannerState::Initial;
-            Some(Token::Finish)
+            Some(TokenKind::Finish)
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_string(self: &mut Scanner, c: u8) -> Option<Token> {
+    fn scan_when_comment(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if self.is_line_end(c) {
+            self.end_line(c, None)
+        } else {
+            None
+        }
+    }
+
+    fn scan_when_string(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
         if c == b'\\' {
             self.state = ScannerState::Escape;
             None
         } else if c == b'\'' {
             self.state = ScannerState::StringEnd;
             None
-        } else if 0x20 <= c && c <= 0x7E {
+        } else if (0x20..=0x7E).contains(&c) {
             if self.push_char(c).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             None
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_number_with_sign(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b'0' {
+    fn scan_when_number_with_sign(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if is_separator(c) {
+            self.state = ScannerState::Initial;
+            self.scanned_operator = Operator::Subtract;
+            Some(TokenKind::Operator)
+        } else if c == b'(' {
+            self.state = ScannerState::Initial;
+            self.scanned_operator = Operator::Subtract;
+            self.queue(Some(TokenKind::Operator), TokenKind::LeftParen)
+        } else if c == b'0' {
+            self.number_has_digits = true;
             self.state = ScannerState::AnyNumber;
             None
-        } else if b'0' <= c && c <= b'9' {
+        } else if c.is_ascii_digit() {
             if self.push_digit(c - b'0', 10).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             self.state = ScannerState::DecimalNumber;
             None
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_any_number(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
-
+    fn scan_when_any_number(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if is_separator(c) {
             self.state = ScannerState::Initial;
-            Some(Token::Number)
-        } else if c == b'\r' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
-
-            self.state = ScannerState::Finish;
-            Some(Token::Number)
+            Some(TokenKind::Number)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::Number)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::Number))
         } else if c == b'b' {
+            self.number_has_digits = false;
             self.state = ScannerState::BinaryNumber;
             None
         } else if c == b'o' {
+            self.number_has_digits = false;
             self.state = ScannerState::OctalNumber;
             None
         } else if c == b'x' {
+            self.number_has_digits = false;
             self.state = ScannerState::HexadecimalNumber;
             None
-        } else if b'0' <= c && c <= b'9' {
+        } else if c.is_ascii_digit() {
             if self.push_digit(c - b'0', 10).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             self.state = ScannerState::DecimalNumber;
             None
         } else if c == b'd' {
+            self.number_has_digits = false;
+            self.state = ScannerState::DecimalNumber;
+            None
+        } else if c == b'_' {
             self.state = ScannerState::DecimalNumber;
             None
+        } else if is_suffix(c) {
+            self.scan_suffix(c)
+        } else if is_operator(c) {
+            self.scan_operator(c, Some(TokenKind::Number))
+        } else if c == b'.' {
+            self.state = ScannerState::Dot;
+            Some(TokenKind::Number)
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_escape(self: &mut Scanner, c: u8) -> Option<Token> {
-        if 0x20 <= c && c <= 0x7E {
-            if self.push_char(c).is_err() {
+    fn scan_when_escape(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        let c = match c {
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'0' => b'\0',
+            b'x' => {
+                self.state = ScannerState::HexEscapeHigh;
+                return None;
+            }
+            // Any other escaped character stands for itself
+            0x20..=0x7E => c,
+            _ => {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
+        };
 
-            self.state = ScannerState::String;
-            None
-        } else {
+        if self.push_char(c).is_err() {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            return Some(TokenKind::Invalid);
         }
+
+        self.state = ScannerState::String;
+        None
     }
 
-    fn scan_when_string_end(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
+    fn scan_when_hex_escape(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        let digit = match (c as char).to_digit(16) {
+            Some(digit) => digit as u8,
+            None => {
+                self.state = ScannerState::Initial;
+                return Some(TokenKind::Invalid);
+            }
+        };
+
+        if let ScannerState::HexEscapeHigh = self.state {
+            self.escaped_byte = digit << 4;
+            self.state = ScannerState::HexEscapeLow;
+            return None;
+        }
+
+        if self.push_char(self.escaped_byte | digit).is_err() {
             self.state = ScannerState::Initial;
-            Some(Token::String)
-        } else if c == b'\r' {
-            self.state = ScannerState::Finish;
-            Some(Token::String)
+            return Some(TokenKind::Invalid);
+        }
+
+        self.state = ScannerState::String;
+        None
+    }
+
+    fn scan_when_string_end(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        // A single quoted character is a number holding its code
+        let token = if self.string_len == 1 {
+            self.clear_scanned_number();
+            self.scanned_magnitude = self.string_buffer[0] as u64;
+            TokenKind::Number
+        } else {
+            TokenKind::String
+        };
+
+        if is_separator(c) {
+            self.state = ScannerState::Initial;
+            Some(token)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(token)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(token))
+        } else if token == TokenKind::Number && is_operator(c) {
+            self.scan_operator(c, Some(token))
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_decimal_number(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
-            if self.finalize_scanned_number().is_err() {
+    fn scan_when_hex_string(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if c == b'"' {
+            if let ScannerState::HexStringLow = self.state {
+                // The last byte is missing its second digit
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
-            self.state = ScannerState::Initial;
-            Some(Token::Number)
-        } else if c == b'\r' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
+            self.state = ScannerState::HexStringEnd;
+            return None;
+        }
 
-            self.state = ScannerState::Finish;
-            Some(Token::Number)
-        } else if b'0' <= c && c <= b'9' {
-            if self.push_digit(c - b'0', 10).is_err() {
+        let digit = match (c as char).to_digit(16) {
+            Some(digit) => digit as u8,
+            None => {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
+        };
 
-            None
+        if let ScannerState::HexString = self.state {
+            self.escaped_byte = digit << 4;
+            self.state = ScannerState::HexStringLow;
+            return None;
+        }
+
+        if self.push_char(self.escaped_byte | digit).is_err() {
+            self.state = ScannerState::Initial;
+            return Some(TokenKind::Invalid);
+        }
+
+        self.state = ScannerState::HexString;
+        None
+    }
+
+    fn scan_when_hex_string_end(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if is_separator(c) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::HexString)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::HexString)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::HexString))
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_binary_number(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
-            if self.finalize_scanned_number().is_err() {
+    fn scan_when_decimal_number(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if self.lacks_digits(c, 10) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Invalid)
+        } else if is_separator(c) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Number)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::Number)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::Number))
+        } else if c.is_ascii_digit() {
+            if self.push_digit(c - b'0', 10).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
+            None
+        } else if c == b'_' {
+            None
+        } else if is_suffix(c) {
+            self.scan_suffix(c)
+        } else if is_operator(c) {
+            self.scan_operator(c, Some(TokenKind::Number))
+        } else if c == b'.' {
+            self.state = ScannerState::Dot;
+            Some(TokenKind::Number)
+        } else {
             self.state = ScannerState::Initial;
-            Some(Token::Number)
-        } else if c == b'\r' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
+            Some(TokenKind::Invalid)
+        }
+    }
 
-            self.state = ScannerState::Finish;
-            Some(Token::Number)
+    fn scan_when_binary_number(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if self.lacks_digits(c, 2) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Invalid)
+        } else if is_separator(c) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Number)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::Number)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::Number))
         } else if c == b'0' || c == b'1' {
             if self.push_digit(c - b'0', 2).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             None
+        } else if c == b'_' {
+            None
+        } else if is_suffix(c) {
+            self.scan_suffix(c)
+        } else if is_operator(c) {
+            self.scan_operator(c, Some(TokenKind::Number))
+        } else if c == b'.' {
+            self.state = ScannerState::Dot;
+            Some(TokenKind::Number)
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_octal_number(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
-
+    fn scan_when_octal_number(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if self.lacks_digits(c, 8) {
             self.state = ScannerState::Initial;
-            Some(Token::Number)
-        } else if c == b'\r' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
-
-            self.state = ScannerState::Finish;
-            Some(Token::Number)
-        } else if b'0' <= c && c <= b'7' {
+            Some(TokenKind::Invalid)
+        } else if is_separator(c) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Number)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::Number)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::Number))
+        } else if (b'0'..=b'7').contains(&c) {
             if self.push_digit(c - b'0', 8).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             None
+        } else if c == b'_' {
+            None
+        } else if is_suffix(c) {
+            self.scan_suffix(c)
+        } else if is_operator(c) {
+            self.scan_operator(c, Some(TokenKind::Number))
+        } else if c == b'.' {
+            self.state = ScannerState::Dot;
+            Some(TokenKind::Number)
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
 
-    fn scan_when_hexadecimal_number(self: &mut Scanner, c: u8) -> Option<Token> {
-        if c == b' ' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
-
+    fn scan_when_hexadecimal_number(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if self.lacks_digits(c, 16) {
             self.state = ScannerState::Initial;
-            Some(Token::Number)
-        } else if c == b'\r' {
-            if self.finalize_scanned_number().is_err() {
-                self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
-            }
-
-            self.state = ScannerState::Finish;
-            Some(Token::Number)
-        } else if b'0' <= c && c <= b'9' {
+            Some(TokenKind::Invalid)
+        } else if is_separator(c) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Number)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::Number)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::Number))
+        } else if c.is_ascii_digit() {
             if self.push_digit(c - b'0', 16).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             None
-        } else if b'a' <= c && c <= b'f' {
+        } else if (b'a'..=b'f').contains(&c) {
             if self.push_digit(c - b'a' + 10, 16).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
             None
-        } else if b'A' <= c && c <= b'F' {
+        } else if (b'A'..=b'F').contains(&c) {
             if self.push_digit(c - b'A' + 10, 16).is_err() {
                 self.state = ScannerState::Initial;
-                return Some(Token::Invalid);
+                return Some(TokenKind::Invalid);
             }
 
+            None
+        } else if c == b'_' {
+            None
+        } else if is_suffix(c) {
+            self.scan_suffix(c)
+        } else if is_operator(c) {
+            self.scan_operator(c, Some(TokenKind::Number))
+        } else if c == b'.' {
+            self.state = ScannerState::Dot;
+            Some(TokenKind::Number)
+        } else {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Invalid)
+        }
+    }
+
+    fn scan_when_suffix(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if is_separator(c) {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Number)
+        } else if c == b'#' {
+            self.state = ScannerState::Comment;
+            Some(TokenKind::Number)
+        } else if self.is_line_end(c) {
+            self.end_line(c, Some(TokenKind::Number))
+        } else if is_operator(c) {
+            self.scan_operator(c, Some(TokenKind::Number))
+        } else if c == b'.' {
+            self.state = ScannerState::Dot;
+            Some(TokenKind::Number)
+        } else {
+            self.state = ScannerState::Initial;
+            Some(TokenKind::Invalid)
+        }
+    }
+
+    fn scan_when_multiplier_suffix(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if c == b'i' {
+            self.state = ScannerState::Suffix;
+            None
+        } else {
+            self.scan_when_suffix(c)
+        }
+    }
+
+    fn scan_when_shift(
+        self: &mut Scanner<N>,
+        c: u8,
+        expected: u8,
+        operator: Operator,
+    ) -> Option<TokenKind> {
+        self.state = ScannerState::Initial;
+        if c == expected {
+            self.scanned_operator = operator;
+            Some(TokenKind::Operator)
+        } else {
+            Some(TokenKind::Invalid)
+        }
+    }
+
+    fn scan_when_after_right_paren(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        self.state = ScannerState::Initial;
+        if c == b'-' {
+            self.scanned_operator = Operator::Subtract;
+            Some(TokenKind::Operator)
+        } else {
+            self.scan_when_initial(c)
+        }
+    }
+
+    fn scan_when_dot(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        if c == b'.' {
+            self.state = ScannerState::DotDot;
             None
         } else {
             self.state = ScannerState::Initial;
-            Some(Token::Invalid)
+            Some(TokenKind::Invalid)
         }
     }
+
+    fn scan_when_dot_dot(self: &mut Scanner<N>, c: u8) -> Option<TokenKind> {
+        self.state = ScannerState::Initial;
+        if c == b'=' {
+            return Some(TokenKind::RangeInclusive);
+        } else if c == b'+' {
+            return Some(TokenKind::RangeLength);
+        }
+
+        // The character after the range operator starts the next token
+        self.next_token_offset = Some(self.offset);
+        if let Some(token) = self.scan_when_initial(c) {
+            self.pending = Some(token);
+        }
+        Some(TokenKind::Range)
+    }
 }
 
 #[cfg(feature = "display")]
-impl core::fmt::Display for Token {
+impl core::fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
-            Token::Identifier => write!(f, "Identifier"),
-            Token::String => write!(f, "String"),
-            Token::Number => write!(f, "Number"),
+            Token::Identifier(name) => write_bytes(f, "Identifier", name),
+            Token::String(bytes) => write_bytes(f, "String", bytes),
+            Token::HexString(bytes) => write_bytes(f, "HexString", bytes),
+            Token::Number(value) => write!(f, "Number({})", value),
+            Token::Operator(operator) => write!(f, "Operator({:?})", operator),
+            Token::LeftParen => write!(f, "LeftParen"),
+            Token::RightParen => write!(f, "RightParen"),
+            Token::Range => write!(f, "Range"),
+            Token::RangeInclusive => write!(f, "RangeInclusive"),
+            Token::RangeLength => write!(f, "RangeLength"),
             Token::Finish => write!(f, "Finish"),
             Token::Invalid => write!(f, "Invalid"),
         }
     }
 }
 
+/// Writes the bytes of a token as text if they are valid UTF-8, and as a list otherwise.
+#[cfg(feature = "display")]
+fn write_bytes(f: &mut Formatter<'_>, name: &str, bytes: &[u8]) -> core::fmt::Result {
+    match core::str::from_utf8(bytes) {
+        Ok(text) => write!(f, "{}({:?})", name, text),
+        Err(_) => write!(f, "{}({:?})", name, bytes),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::scanner::{Scanner, Token};
+    use crate::scanner::{LineEnding, Operator, Scanner, Token, TokenKind};
 
     fn expect_first_token(scanner: &mut Scanner, input: &str, expected: Token) {
         for c in input.as_bytes() {
-            let res = scanner.scan_command(*c);
-
-            if res.is_some() {
-                assert_eq!(res.unwrap(), expected);
+            if let Some(res) = scanner.scan_command(*c) {
+                assert_eq!(res, expected);
                 return;
             }
         }
@@ -469,108 +1180,792 @@ mod test {
         panic!("Should yield at least one token!");
     }
 
-    fn expect_scanned_string(scanner: &Scanner, expected: &str) {
-        let expected_slice = expected.as_bytes();
-        for i in 0..expected_slice.len() {
-            assert_eq!(scanner.scanned_string[i], expected_slice[i]);
+    fn expect_tokens(scanner: &mut Scanner, input: &str, expected: &[TokenKind]) {
+        let mut tokens = Vec::new();
+        for c in input.as_bytes() {
+            if let Some(res) = scanner.scan(*c) {
+                tokens.push(res);
+            }
+            if let Some(res) = scanner.take_pending_kind() {
+                tokens.push(res);
+            }
         }
-        assert_eq!(scanner.scanned_string[expected_slice.len()], b'\0');
+
+        assert_eq!(tokens, expected);
+    }
+
+    fn scan_command_tokens(scanner: &mut Scanner, input: &str) -> Vec<(String, usize)> {
+        let mut tokens = Vec::new();
+        for c in input.as_bytes() {
+            if let Some(token) = scanner.scan_command(*c) {
+                let token = format!("{:?}", token);
+                tokens.push((token, scanner.token_offset()));
+            }
+        }
+
+        tokens
+    }
+
+    #[test]
+    fn scan_pending_token_without_take_pending() {
+        let mut scanner = Scanner::default();
+        let tokens = scan_command_tokens(&mut scanner, "1+2)*3 \r\n");
+        let expected = [
+            ("Number(1)", 0),
+            ("Operator(Add)", 1),
+            ("Number(2)", 2),
+            ("RightParen", 3),
+            ("Operator(Multiply)", 4),
+            ("Number(3)", 5),
+            ("Finish", 7),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(token, offset)| (token.to_string(), *offset))
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn query_position_while_holding_token() {
+        let mut scanner = Scanner::new();
+        let kind = b"ab ".iter().find_map(|c| scanner.scan(*c)).unwrap();
+
+        let token = scanner.token(kind);
+        assert_eq!(token, Token::Identifier(b"ab"));
+        assert_eq!((scanner.token_offset(), scanner.token_line()), (0, 0));
     }
 
     #[test]
     fn scan_binary_number() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "0b1010\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, 0b1010);
+        expect_first_token(&mut scanner, "0b1010\r\n", Token::Number(0b1010));
     }
 
     #[test]
     fn scan_octal_number() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "0o755\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, 0o755);
+        expect_first_token(&mut scanner, "0o755\r\n", Token::Number(0o755));
     }
 
     #[test]
     fn scan_hexadecimal_number() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "0x1234ABCD\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, 0x1234ABCD);
+        expect_first_token(&mut scanner, "0x1234ABCD\r\n", Token::Number(0x1234ABCD));
     }
 
     #[test]
     fn scan_decimal_number_0() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "123\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, 123);
+        expect_first_token(&mut scanner, "123\r\n", Token::Number(123));
     }
 
     #[test]
     fn scan_decimal_number_1() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "0109\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, 109);
+        expect_first_token(&mut scanner, "0109\r\n", Token::Number(109));
     }
 
     #[test]
     fn scan_decimal_number_2() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "0\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, 0);
+        expect_first_token(&mut scanner, "0\r\n", Token::Number(0));
     }
 
     #[test]
     fn scan_decimal_number_3() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "3\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, 3);
+        expect_first_token(&mut scanner, "3\r\n", Token::Number(3));
     }
 
     #[test]
     fn scan_decimal_number_4() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "-128\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, -128);
+        expect_first_token(&mut scanner, "-128\r\n", Token::Number(-128));
+    }
+
+    #[test]
+    fn scan_most_negative_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "-0x80000000\r\n",
+            Token::Number(i32::MIN as i128),
+        );
+
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "-0x8000000000000000\r\n",
+            Token::Number(i64::MIN as i128),
+        );
+
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "-0xFFFF_FFFF_FFFF_FFFF\r\n",
+            Token::Number(-(u64::MAX as i128)),
+        );
+        assert!(!scanner.number_overflowed());
+    }
+
+    #[test]
+    fn scan_unsigned_64bit_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "0x8000000000000000\r\n",
+            Token::Number(0x8000_0000_0000_0000),
+        );
+
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "0xFFFF_FFFF_FFFF_FFFF\r\n",
+            Token::Number(u64::MAX as i128),
+        );
+        assert!(!scanner.number_overflowed());
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0x1_0000_0000_0000_0000\r\n", Token::Invalid);
+        assert!(scanner.number_overflowed());
+    }
+
+    #[test]
+    fn scan_unsigned_32bit_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0xFFFFFFFF\r\n", Token::Number(0xFFFFFFFF));
+        assert!(!scanner.number_overflowed());
+    }
+
+    #[test]
+    fn scan_number_with_separators() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0x0001_0000\r\n", Token::Number(0x10000));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "1_000_000\r\n", Token::Number(1000000));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0b1010_0101\r\n", Token::Number(0b1010_0101));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0o7_55\r\n", Token::Number(0o755));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0_\r\n", Token::Number(0));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0x_\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0b_ ", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0o_+1 ", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0x ", Token::Invalid);
+    }
+
+    #[test]
+    fn scan_number_with_suffix() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "64K\r\n", Token::Number(64 * 1024));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "2Ki\r\n", Token::Number(2048));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "4k ", Token::Number(4096));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "4ki ", Token::Number(4096));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "3Mi\r\n", Token::Number(3 * 1024 * 1024));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "1m+1\r\n", Token::Number(1024 * 1024));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0x10M#", Token::Number(16 * 1024 * 1024));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "-1K\r\n", Token::Number(-1024));
+    }
+
+    #[test]
+    fn scan_number_with_invalid_suffix() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "2Kii\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "2Mk\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "2KK\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "2K1\r\n", Token::Invalid);
+        assert!(!scanner.number_overflowed());
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0xFFFF_FFFF_FFFF_FFFFK\r\n", Token::Invalid);
+        assert!(scanner.number_overflowed());
+    }
+
+    #[test]
+    fn scan_expression() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "0x100+3*(32-1)\r\n",
+            &[
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::LeftParen,
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::RightParen,
+                TokenKind::Finish,
+            ],
+        );
+
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "1 << 2K >>1|2&3/4\r\n",
+            &[
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Finish,
+            ],
+        );
+    }
+
+    #[test]
+    fn scan_minus_operator() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "- 1\r\n", Token::Operator(Operator::Subtract));
+
+        // A minus directly after an operand is an operator, otherwise it is the sign of a number
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "1-1 -1 (1)-1 -(1)\r\n",
+            &[
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::LeftParen,
+                TokenKind::Number,
+                TokenKind::RightParen,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::LeftParen,
+                TokenKind::Number,
+                TokenKind::RightParen,
+                TokenKind::Finish,
+            ],
+        );
+    }
+
+    #[test]
+    fn scan_operator_offset() {
+        let mut scanner = Scanner::new();
+        let mut offsets = Vec::new();
+        for c in "12<<3*4\r\n".bytes() {
+            if scanner.scan_command(c).is_some() {
+                offsets.push(scanner.token_offset());
+            }
+            if scanner.take_pending().is_some() {
+                offsets.push(scanner.token_offset());
+            }
+        }
+
+        assert_eq!(offsets, [0, 2, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn scan_range() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "0x10..0x30 1K..=2K 0x10..+32 4 .. (5)\r\n",
+            &[
+                TokenKind::Number,
+                TokenKind::Range,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::RangeInclusive,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::RangeLength,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Range,
+                TokenKind::LeftParen,
+                TokenKind::Number,
+                TokenKind::RightParen,
+                TokenKind::Finish,
+            ],
+        );
+
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "1.2",
+            &[TokenKind::Number, TokenKind::Invalid],
+        );
+    }
+
+    #[test]
+    fn scan_range_offset() {
+        let mut scanner = Scanner::new();
+        let mut offsets = Vec::new();
+        for c in "16..32 1..=2 3..+4\r\n".bytes() {
+            if scanner.scan_command(c).is_some() {
+                offsets.push(scanner.token_offset());
+            }
+            if scanner.take_pending().is_some() {
+                offsets.push(scanner.token_offset());
+            }
+        }
+
+        assert_eq!(offsets, [0, 2, 4, 7, 8, 11, 13, 14, 17, 18]);
+    }
+
+    #[test]
+    fn scan_invalid_operator() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "<2\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_tokens(&mut scanner, "1(", &[TokenKind::Invalid]);
+    }
+
+    #[test]
+    fn scan_overflowing_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "18446744073709551615\r\n",
+            Token::Number(u64::MAX as i128),
+        );
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "18446744073709551616\r\n", Token::Invalid);
+        assert!(scanner.number_overflowed());
+        assert_eq!(scanner.token_offset(), 0);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0x1G\r\n", Token::Invalid);
+        assert!(!scanner.number_overflowed());
     }
 
     #[test]
     fn scan_negative_number() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "-0x42\r\n", Token::Number);
-        assert_eq!(scanner.scanned_number, -0x42);
+        expect_first_token(&mut scanner, "-0x42\r\n", Token::Number(-0x42));
     }
 
     #[test]
     fn scan_identifier_0() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "abcde\r\n", Token::Identifier);
-        expect_scanned_string(&scanner, "abcde");
+        expect_first_token(&mut scanner, "abcde\r\n", Token::Identifier(b"abcde"));
     }
 
     #[test]
     fn scan_identifier_1() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "hoge_\r\n", Token::Identifier);
-        expect_scanned_string(&scanner, "hoge_");
+        expect_first_token(&mut scanner, "hoge_\r\n", Token::Identifier(b"hoge_"));
     }
 
     #[test]
     fn scan_finish_0() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "\r\n\r\n", Token::Finish);
+        expect_tokens(&mut scanner, "\r\n\r\n", &[]);
+        assert_eq!(scanner.line(), 2);
+    }
+
+    #[test]
+    fn scan_finish_1() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "rb\r\n",
+            &[TokenKind::Identifier, TokenKind::Finish],
+        );
     }
 
     #[test]
     fn scan_string_0() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "'Hello, world!'\r\n", Token::String);
-        expect_scanned_string(&scanner, "Hello, world!");
+        expect_first_token(
+            &mut scanner,
+            "'Hello, world!'\r\n",
+            Token::String(b"Hello, world!"),
+        );
     }
 
     #[test]
     fn scan_string_1() {
         let mut scanner = Scanner::default();
-        expect_first_token(&mut scanner, "'That\\'s Right!'\r\n", Token::String);
-        expect_scanned_string(&scanner, "That's Right!");
+        expect_first_token(
+            &mut scanner,
+            "'That\\'s Right!'\r\n",
+            Token::String(b"That's Right!"),
+        );
+    }
+
+    #[test]
+    fn scan_string_with_escapes() {
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "'a\\n\\r\\t\\0\\\\\\q'\r\n",
+            Token::String(b"a\n\r\t\0\\q"),
+        );
+
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "'\\x00\\xfF\\x7e1'\r\n",
+            Token::String(b"\x00\xFF\x7E1"),
+        );
+    }
+
+    #[test]
+    fn scan_character_literal() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "'A'\r\n", Token::Number(0x41));
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "'\\n' ", Token::Number(0x0A));
+
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "'a'-'A'\r\n",
+            &[
+                TokenKind::Number,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Finish,
+            ],
+        );
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "'' ", Token::String(b""));
+    }
+
+    #[test]
+    fn scan_hex_string() {
+        let mut scanner = Scanner::default();
+        expect_first_token(
+            &mut scanner,
+            "h\"DEADbeef00\"\r\n",
+            Token::HexString(b"\xDE\xAD\xBE\xEF\x00"),
+        );
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "H\"\" ", Token::HexString(b""));
+    }
+
+    #[test]
+    fn scan_invalid_hex_string() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "h\"ABC\"\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "h\"AG\"\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "x\"AB\"\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "h\"AB\"+\r\n", Token::Invalid);
+    }
+
+    #[test]
+    fn scan_string_with_invalid_escape() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "'\\xG0'\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "'\\x1'\r\n", Token::Invalid);
+
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "'\\\t'\r\n", Token::Invalid);
+    }
+
+    #[test]
+    fn track_token_offset() {
+        let mut scanner = Scanner::new();
+        let expected = [
+            (Token::Identifier(b"wb"), 0),
+            (Token::Number(0x10), 4),
+            (Token::Number(0x42), 9),
+            (Token::Finish, 13),
+        ];
+        let mut n = 0;
+        for c in "wb  0x10 0x42\r\n".as_bytes() {
+            if let Some(res) = scanner.scan_command(*c) {
+                assert_eq!(res, expected[n].0);
+                assert_eq!(scanner.token_offset(), expected[n].1);
+                n += 1;
+            }
+        }
+
+        assert_eq!(n, expected.len());
+        assert_eq!(scanner.line(), 1);
+        assert_eq!(scanner.token_line(), 0);
+    }
+
+    #[test]
+    fn scan_crlf_only_by_default() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "rb 1\n",
+            &[TokenKind::Identifier, TokenKind::Invalid],
+        );
+    }
+
+    #[test]
+    fn scan_lf_line_ending() {
+        let mut scanner = Scanner::default();
+        scanner.set_line_ending(LineEnding::Lf);
+        expect_tokens(
+            &mut scanner,
+            "rb 1\nrb\n\n",
+            &[
+                TokenKind::Identifier,
+                TokenKind::Number,
+                TokenKind::Finish,
+                TokenKind::Identifier,
+                TokenKind::Finish,
+            ],
+        );
+        assert_eq!(scanner.line(), 3);
+    }
+
+    #[test]
+    fn scan_cr_line_ending() {
+        let mut scanner = Scanner::default();
+        scanner.set_line_ending(LineEnding::Cr);
+        expect_tokens(
+            &mut scanner,
+            "rb 1\rrb 2 \r",
+            &[
+                TokenKind::Identifier,
+                TokenKind::Number,
+                TokenKind::Finish,
+                TokenKind::Identifier,
+                TokenKind::Number,
+                TokenKind::Finish,
+            ],
+        );
+        expect_tokens(&mut scanner, "\n", &[TokenKind::Invalid]);
+    }
+
+    #[test]
+    fn scan_any_line_ending() {
+        let mut scanner = Scanner::default();
+        scanner.set_line_ending(LineEnding::Any);
+        expect_tokens(
+            &mut scanner,
+            "a\r\nb\nc\rd\n\r",
+            &[
+                TokenKind::Identifier,
+                TokenKind::Finish,
+                TokenKind::Identifier,
+                TokenKind::Finish,
+                TokenKind::Identifier,
+                TokenKind::Finish,
+                TokenKind::Identifier,
+                TokenKind::Finish,
+            ],
+        );
+        assert_eq!(scanner.line(), 5);
+    }
+
+    #[test]
+    fn scan_invalid_line_end() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "'ab\r\n-\r\nrb\r\r\n",
+            &[
+                TokenKind::Invalid,
+                TokenKind::Finish,
+                TokenKind::Invalid,
+                TokenKind::Finish,
+                TokenKind::Identifier,
+                TokenKind::Invalid,
+                TokenKind::Finish,
+            ],
+        );
+        assert_eq!(scanner.line(), 3);
+
+        let mut scanner = Scanner::default();
+        scanner.set_line_ending(LineEnding::Lf);
+        expect_tokens(
+            &mut scanner,
+            "'ab\n'a\\\n",
+            &[
+                TokenKind::Invalid,
+                TokenKind::Finish,
+                TokenKind::Invalid,
+                TokenKind::Finish,
+            ],
+        );
+        assert_eq!(scanner.line(), 2);
+    }
+
+    #[test]
+    fn scan_separators_when_initial() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            " \t rb \t\r\n",
+            &[TokenKind::Identifier, TokenKind::Finish],
+        );
+    }
+
+    #[test]
+    fn scan_separators_after_identifier() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "sd\tx256\t\r\n",
+            &[
+                TokenKind::Identifier,
+                TokenKind::Identifier,
+                TokenKind::Finish,
+            ],
+        );
+        assert_eq!(scanner.scanned_string(), b"x256");
+    }
+
+    #[test]
+    fn scan_separators_after_string() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "'ab'\t'cd'\t\r\n",
+            &[TokenKind::String, TokenKind::String, TokenKind::Finish],
+        );
+    }
+
+    #[test]
+    fn scan_separators_after_numbers() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "0\t12\t0b1\t0o7\t0x1F\t \r\n",
+            &[
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Finish,
+            ],
+        );
+        assert_eq!(scanner.scanned_number(), 0x1F);
+    }
+
+    #[test]
+    fn scan_separators_after_decimal_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "123\t", Token::Number(123));
+    }
+
+    #[test]
+    fn scan_separators_after_binary_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0b101\t", Token::Number(0b101));
+    }
+
+    #[test]
+    fn scan_separators_after_octal_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "0o17\t", Token::Number(0o17));
+    }
+
+    #[test]
+    fn scan_separators_after_hexadecimal_number() {
+        let mut scanner = Scanner::default();
+        expect_first_token(&mut scanner, "-0xAb\t", Token::Number(-0xAB));
+    }
+
+    #[test]
+    fn scan_comment() {
+        let mut scanner = Scanner::default();
+        expect_tokens(
+            &mut scanner,
+            "# recipe\r\n\r\nsd x256  # main config chip\r\nrb 0x10# 'x' \\\r\n",
+            &[
+                TokenKind::Identifier,
+                TokenKind::Identifier,
+                TokenKind::Finish,
+                TokenKind::Identifier,
+                TokenKind::Number,
+                TokenKind::Finish,
+            ],
+        );
+        assert_eq!(scanner.line(), 4);
+    }
+
+    #[test]
+    fn scan_comment_with_lf_line_ending() {
+        let mut scanner = Scanner::default();
+        scanner.set_line_ending(LineEnding::Lf);
+        expect_tokens(
+            &mut scanner,
+            "\n#\nrb 1 #\n",
+            &[TokenKind::Identifier, TokenKind::Number, TokenKind::Finish],
+        );
+    }
+
+    #[test]
+    fn scan_with_custom_buffer_size() {
+        fn scan<const N: usize>(input: &str, expected: Token) {
+            let mut scanner = Scanner::<N>::default();
+            for c in input.bytes() {
+                if let Some(token) = scanner.scan_command(c) {
+                    assert_eq!(token, expected);
+                    return;
+                }
+            }
+
+            panic!("Should yield at least one token!");
+        }
+
+        scan::<4>("'abcd' ", Token::String(b"abcd"));
+        scan::<4>("'abcde' ", Token::Invalid);
+        scan::<4>("abcd ", Token::Identifier(b"abcd"));
+        scan::<4>("abcde ", Token::Invalid);
+        scan::<256>("abcdefghijk ", Token::Identifier(b"abcdefghijk"));
+        scan::<64>(
+            "'The quick brown fox jumps over the lazy dog' ",
+            Token::String(b"The quick brown fox jumps over the lazy dog"),
+        );
     }
 }
@@ -1,17 +1,209 @@
-use core::cmp::min;
+use crate::scanner::SCANNED_STRING_BUFFER_SIZE;
 
+/// Returns true if the two byte strings are equal over their whole length.
 pub fn u8_str_equal(a: &[u8], b: &[u8]) -> bool {
-    let len = min(a.len(), b.len());
+    a == b
+}
+
+/// Returns true if the two byte strings are equal over their whole length, ignoring the case of
+/// ASCII letters.
+pub fn u8_str_equal_ignore_case(a: &[u8], b: &[u8]) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+/// The default capacity of a `ByteBuffer`.
+///
+/// It matches the default capacity of the scanner, so that the payload of a command parsed by
+/// `Parser::new` is a `ByteBuffer` of the default capacity.
+pub const BYTE_BUFFER_SIZE: usize = SCANNED_STRING_BUFFER_SIZE;
+
+/// The error returned when a `ByteBuffer` cannot hold any more bytes.
+#[derive(PartialEq, Debug)]
+pub struct CapacityError;
+
+/// A buffer of up to `N` bytes which does not need an allocator.
+#[derive(Clone, Copy, Debug)]
+pub struct ByteBuffer<const N: usize = BYTE_BUFFER_SIZE> {
+    data: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for ByteBuffer<N> {
+    fn default() -> ByteBuffer<N> {
+        ByteBuffer {
+            data: [0; N],
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> ByteBuffer<N> {
+    pub fn try_from_slice(bytes: &[u8]) -> Result<ByteBuffer<N>, CapacityError> {
+        let mut buffer = ByteBuffer::default();
+        for b in bytes {
+            buffer.push(*b)?;
+        }
+
+        Ok(buffer)
+    }
+
+    pub fn push(&mut self, b: u8) -> Result<(), CapacityError> {
+        if self.len >= N {
+            return Err(CapacityError);
+        }
+
+        self.data[self.len] = b;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<const N: usize> PartialEq for ByteBuffer<N> {
+    fn eq(&self, other: &ByteBuffer<N>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+/// The error returned when a byte is not a 7-bit bus address.
+#[derive(PartialEq, Debug)]
+pub struct AddressError;
+
+/// A set of 7-bit bus addresses, such as the ones which acknowledged a probe.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AddressSet {
+    bits: u128,
+}
+
+impl AddressSet {
+    pub fn try_from_slice(addresses: &[u8]) -> Result<AddressSet, AddressError> {
+        let mut set = AddressSet::default();
+        for address in addresses {
+            set.insert(*address)?;
+        }
+
+        Ok(set)
+    }
 
-    for i in 0..len {
-        if a[i] != b[i] {
-            return false;
+    pub fn insert(&mut self, address: u8) -> Result<(), AddressError> {
+        if address > 0x7F {
+            return Err(AddressError);
         }
 
-        if a[i] == b'\0' {
-            return true;
+        self.bits |= 1 << address;
+        Ok(())
+    }
+
+    pub fn contains(&self, address: u8) -> bool {
+        address <= 0x7F && self.bits & (1 << address) != 0
+    }
+
+    /// Returns the addresses in the set in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=0x7F).filter(move |address| self.contains(*address))
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+}
+
+/// An incremental CRC-32 (IEEE 802.3) calculator, as used by the `verify` and `crc` commands.
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32 {
+    value: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32 { value: 0xFFFFFFFF }
+    }
+}
+
+impl Crc32 {
+    pub fn update(&mut self, data: &[u8]) {
+        for b in data {
+            self.value ^= *b as u32;
+            for _ in 0..8 {
+                if self.value & 1 != 0 {
+                    self.value = (self.value >> 1) ^ 0xEDB88320;
+                } else {
+                    self.value >>= 1;
+                }
+            }
         }
     }
 
-    return false;
+    pub fn finish(&self) -> u32 {
+        !self.value
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::default();
+    crc.update(data);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::{
+        crc32, u8_str_equal, u8_str_equal_ignore_case, AddressError, AddressSet, Crc32,
+    };
+
+    #[test]
+    fn compare_strings() {
+        assert!(u8_str_equal(b"rb", b"rb"));
+        assert!(!u8_str_equal(b"rb", b"RB"));
+        assert!(!u8_str_equal(b"rb", b"rb\0"));
+        assert!(u8_str_equal_ignore_case(b"x256", b"X256"));
+        assert!(u8_str_equal_ignore_case(b"", b""));
+        assert!(!u8_str_equal_ignore_case(b"rb", b"rb\0zz"));
+        assert!(!u8_str_equal_ignore_case(b"rb", b"r"));
+    }
+
+    #[test]
+    fn collect_addresses() {
+        let mut addresses = AddressSet::default();
+        assert!(addresses.is_empty());
+        assert_eq!(addresses.insert(0x7F), Ok(()));
+        assert_eq!(addresses.insert(0x00), Ok(()));
+        assert_eq!(addresses.insert(0x50), Ok(()));
+        assert_eq!(addresses.insert(0x80), Err(AddressError));
+
+        assert_eq!(addresses.len(), 3);
+        assert!(addresses.contains(0x50));
+        assert!(!addresses.contains(0x51));
+        assert!(!addresses.contains(0xD0));
+        assert!(addresses.iter().eq([0x00, 0x50, 0x7F].iter().copied()));
+    }
+
+    #[test]
+    fn compute_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn compute_crc32_incrementally() {
+        let mut crc = Crc32::default();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xCBF43926);
+    }
 }